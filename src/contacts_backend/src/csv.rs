@@ -0,0 +1,478 @@
+use candid::{CandidType, Deserialize};
+
+use crate::data::contact::Contact;
+use crate::data::labelled_value::LabelledValue;
+use crate::data::new_contact::NewContact;
+use crate::data::postal_address::PostalAddress;
+use crate::data::structured_name::StructuredName;
+
+/// Column layout of exported CSV files.
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CsvLayout {
+    /// Google Contacts.
+    Google,
+    /// Outlook and Outlook.com.
+    Outlook,
+}
+
+/// Headers recognised on import, normalised by `normalize_header`, for each contact field.
+const FULL_NAME_HEADERS: &[&str] = &["name", "fullname", "displayname"];
+const NAME_PREFIX_HEADERS: &[&str] = &["nameprefix", "title"];
+const GIVEN_NAME_HEADERS: &[&str] = &["givenname", "firstname"];
+const MIDDLE_NAME_HEADERS: &[&str] = &["additionalname", "middlename"];
+const FAMILY_NAME_HEADERS: &[&str] = &["familyname", "lastname", "surname"];
+const NAME_SUFFIX_HEADERS: &[&str] = &["namesuffix", "suffix"];
+const ORGANIZATION_HEADERS: &[&str] = &["organizationname", "organization1name", "company", "organization"];
+const JOB_TITLE_HEADERS: &[&str] = &["organizationtitle", "organization1title", "jobtitle"];
+/// Suffixes of the column holding a numbered value's label: current Google exports use
+/// "E-mail 1 - Label", older ones "E-mail 1 - Type".
+const LABEL_SUFFIXES: [&str; 2] = ["label", "type"];
+/// Unnumbered email and phone headers with the label their values get.
+const EMAIL_HEADERS: &[(&str, &str)] = &[
+    ("emailaddress", ""),
+    ("email", ""),
+    ("email2address", ""),
+    ("email3address", ""),
+];
+const PHONE_HEADERS: &[(&str, &str)] = &[
+    ("primaryphone", ""),
+    ("mobilephone", "mobile"),
+    ("homephone", "home"),
+    ("businessphone", "work"),
+    ("phone", ""),
+    ("phonenumber", ""),
+    ("telephone", ""),
+];
+/// Outlook address header prefixes with the label their addresses get.
+const ADDRESS_PREFIXES: &[(&str, &str)] = &[("home", "home"), ("business", "work"), ("other", "other")];
+
+/// Google numbers repeated fields from 1, e.g. "E-mail 2 - Value"; higher numbers are
+/// not looked for on import.
+const MAX_NUMBERED_COLUMNS: usize = 10;
+
+/// Serialize contacts as CSV with a header row in the given layout.
+///
+/// The Google layout has as many numbered email, phone and address columns as the contact
+/// with the most of each needs. The Outlook layout has fixed columns: three emails, a
+/// mobile, home and business phone, and a home and business address, which labelled values
+/// fill first and unlabelled ones fill in order.
+pub fn to_csv(contacts: &[Contact], layout: CsvLayout) -> String {
+    match layout {
+        CsvLayout::Google => to_google_csv(contacts),
+        CsvLayout::Outlook => to_outlook_csv(contacts),
+    }
+}
+
+fn to_google_csv(contacts: &[Contact]) -> String {
+    let count = |len: fn(&Contact) -> usize| contacts.iter().map(len).max().unwrap_or(0).max(1);
+    let emails = count(|c| c.emails.len());
+    let phones = count(|c| c.phones.len());
+    let addresses = count(|c| c.addresses.len());
+
+    let mut header: Vec<String> = [
+        "Name",
+        "Name Prefix",
+        "Given Name",
+        "Additional Name",
+        "Family Name",
+        "Name Suffix",
+        "Organization 1 - Name",
+        "Organization 1 - Title",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect();
+    for n in 1..=emails {
+        header.extend([format!("E-mail {} - Type", n), format!("E-mail {} - Value", n)]);
+    }
+    for n in 1..=phones {
+        header.extend([format!("Phone {} - Type", n), format!("Phone {} - Value", n)]);
+    }
+    for n in 1..=addresses {
+        for part in ["Type", "Street", "City", "Region", "Postal Code", "Country"] {
+            header.push(format!("Address {} - {}", n, part));
+        }
+    }
+    let mut csv = write_record(header.iter().map(String::as_str));
+
+    for contact in contacts {
+        let name = &contact.structured_name;
+        let mut record: Vec<&str> = vec![
+            &contact.name,
+            &name.prefix,
+            &name.given,
+            &name.additional,
+            &name.family,
+            &name.suffix,
+            &contact.organization,
+            &contact.title,
+        ];
+        for (values, columns) in [(&contact.emails, emails), (&contact.phones, phones)] {
+            for n in 0..columns {
+                match values.get(n) {
+                    Some(value) => record.extend([value.label.as_str(), &value.value]),
+                    None => record.extend(["", ""]),
+                }
+            }
+        }
+        for n in 0..addresses {
+            match contact.addresses.get(n) {
+                Some(a) => record.extend([
+                    a.label.as_str(),
+                    &a.street,
+                    &a.locality,
+                    &a.region,
+                    &a.postal_code,
+                    &a.country,
+                ]),
+                None => record.extend(["", "", "", "", "", ""]),
+            }
+        }
+        csv.push_str(&write_record(record));
+    }
+    csv
+}
+
+fn to_outlook_csv(contacts: &[Contact]) -> String {
+    const PHONE_LABELS: [&str; 3] = ["mobile", "home", "work"];
+    const ADDRESS_LABELS: [&str; 2] = ["home", "work"];
+
+    let header = [
+        "Title",
+        "First Name",
+        "Middle Name",
+        "Last Name",
+        "Suffix",
+        "Company",
+        "Job Title",
+        "E-mail Address",
+        "E-mail 2 Address",
+        "E-mail 3 Address",
+        "Mobile Phone",
+        "Home Phone",
+        "Business Phone",
+        "Home Street",
+        "Home City",
+        "Home State",
+        "Home Postal Code",
+        "Home Country/Region",
+        "Business Street",
+        "Business City",
+        "Business State",
+        "Business Postal Code",
+        "Business Country/Region",
+    ];
+    let mut csv = write_record(header);
+
+    for contact in contacts {
+        let name = &contact.structured_name;
+        let mut record: Vec<&str> = vec![
+            &name.prefix,
+            &name.given,
+            &name.additional,
+            &name.family,
+            &name.suffix,
+            &contact.organization,
+            &contact.title,
+        ];
+        let emails: Vec<&str> = contact.emails.iter().map(|e| e.value.as_str()).collect();
+        record.extend((0..3).map(|n| emails.get(n).copied().unwrap_or("")));
+        let phones = fill_slots(&contact.phones, &PHONE_LABELS, |p| &p.label);
+        record.extend(phones.iter().map(|p| p.map_or("", |p| p.value.as_str())));
+        for address in fill_slots(&contact.addresses, &ADDRESS_LABELS, |a| &a.label) {
+            match address {
+                Some(a) => record.extend([a.street.as_str(), &a.locality, &a.region, &a.postal_code, &a.country]),
+                None => record.extend(["", "", "", "", ""]),
+            }
+        }
+        csv.push_str(&write_record(record));
+    }
+    csv
+}
+
+/// Assign values to fixed slots: each slot takes the first value with its label, then the
+/// remaining slots take the leftover values in order. Values beyond the slots are dropped.
+fn fill_slots<'a, T>(values: &'a [T], slots: &[&str], label: impl Fn(&T) -> &str) -> Vec<Option<&'a T>> {
+    let mut used = vec![false; values.len()];
+    let mut filled: Vec<Option<&T>> = slots
+        .iter()
+        .map(|slot| {
+            let i = (0..values.len()).find(|&i| !used[i] && label(&values[i]).eq_ignore_ascii_case(slot))?;
+            used[i] = true;
+            Some(&values[i])
+        })
+        .collect();
+    let mut leftovers = (0..values.len()).filter(|&i| !used[i]);
+    for slot in filled.iter_mut().filter(|slot| slot.is_none()) {
+        *slot = leftovers.next().map(|i| &values[i]);
+    }
+    filled
+}
+
+/// Parse CSV text with a header row into contact details, one entry per data row.
+///
+/// The header is matched against common Google Contacts and Outlook column names. Returns
+/// an error if no name column is recognised; rows that cannot be parsed or have no name
+/// are reported individually.
+pub fn parse_csv(text: &str) -> Result<Vec<Result<NewContact, String>>, String> {
+    let mut records = parse_records(text).into_iter();
+    let header = match records.next() {
+        Some(Ok(header)) => header,
+        Some(Err(reason)) => return Err(format!("unreadable header: {}", reason)),
+        None => return Err("missing header row".to_string()),
+    };
+    let columns = ColumnMap::detect(&header)?;
+
+    Ok(records
+        .map(|record| record.and_then(|fields| columns.to_contact(&fields)))
+        .collect())
+}
+
+/// Where a value's label comes from: a type column, as in Google's layout, or the column
+/// itself, as in Outlook's.
+enum LabelSource {
+    Column(usize),
+    Fixed(&'static str),
+}
+
+/// Columns holding an email or phone.
+struct ValueColumns {
+    label: LabelSource,
+    value: usize,
+}
+
+/// Columns holding the parts of an address.
+struct AddressColumns {
+    label: LabelSource,
+    street: Option<usize>,
+    locality: Option<usize>,
+    region: Option<usize>,
+    postal_code: Option<usize>,
+    country: Option<usize>,
+}
+
+/// Positions of the recognised columns in a header row.
+struct ColumnMap {
+    full_name: Option<usize>,
+    name_prefix: Option<usize>,
+    given_name: Option<usize>,
+    middle_name: Option<usize>,
+    family_name: Option<usize>,
+    name_suffix: Option<usize>,
+    organization: Option<usize>,
+    job_title: Option<usize>,
+    emails: Vec<ValueColumns>,
+    phones: Vec<ValueColumns>,
+    addresses: Vec<AddressColumns>,
+}
+
+impl ColumnMap {
+    fn detect(header: &[String]) -> Result<Self, String> {
+        let normalized: Vec<String> = header.iter().map(|h| normalize_header(h)).collect();
+        let position = |name: &str| normalized.iter().position(|h| h == name);
+        let find = |names: &[&str]| names.iter().find_map(|n| position(n));
+        let label_source = |prefix: &str| {
+            match LABEL_SUFFIXES.iter().find_map(|suffix| position(&format!("{}{}", prefix, suffix))) {
+                Some(column) => LabelSource::Column(column),
+                None => LabelSource::Fixed(""),
+            }
+        };
+        let numbered_values = |field: &str| -> Vec<ValueColumns> {
+            (1..=MAX_NUMBERED_COLUMNS)
+                .filter_map(|n| {
+                    Some(ValueColumns {
+                        value: position(&format!("{}{}value", field, n))?,
+                        label: label_source(&format!("{}{}", field, n)),
+                    })
+                })
+                .collect()
+        };
+        let fixed_values = |headers: &[(&str, &'static str)]| -> Vec<ValueColumns> {
+            headers
+                .iter()
+                .filter_map(|&(name, label)| {
+                    Some(ValueColumns {
+                        value: position(name)?,
+                        label: LabelSource::Fixed(label),
+                    })
+                })
+                .collect()
+        };
+
+        let mut emails = numbered_values("email");
+        emails.extend(fixed_values(EMAIL_HEADERS));
+        let mut phones = numbered_values("phone");
+        phones.extend(fixed_values(PHONE_HEADERS));
+
+        let mut addresses: Vec<AddressColumns> = (1..=MAX_NUMBERED_COLUMNS)
+            .map(|n| {
+                let part = |name: &str| position(&format!("address{}{}", n, name));
+                AddressColumns {
+                    label: label_source(&format!("address{}", n)),
+                    street: part("street"),
+                    locality: part("city"),
+                    region: part("region"),
+                    postal_code: part("postalcode"),
+                    country: part("country"),
+                }
+            })
+            .collect();
+        addresses.extend(ADDRESS_PREFIXES.iter().map(|&(prefix, label)| {
+            let part = |name: &str| position(&format!("{}{}", prefix, name));
+            AddressColumns {
+                label: LabelSource::Fixed(label),
+                street: part("street"),
+                locality: part("city"),
+                region: part("state"),
+                postal_code: part("postalcode"),
+                country: part("countryregion"),
+            }
+        }));
+        addresses.retain(|a| {
+            [a.street, a.locality, a.region, a.postal_code, a.country].iter().any(Option::is_some)
+        });
+
+        let columns = Self {
+            full_name: find(FULL_NAME_HEADERS),
+            name_prefix: find(NAME_PREFIX_HEADERS),
+            given_name: find(GIVEN_NAME_HEADERS),
+            middle_name: find(MIDDLE_NAME_HEADERS),
+            family_name: find(FAMILY_NAME_HEADERS),
+            name_suffix: find(NAME_SUFFIX_HEADERS),
+            organization: find(ORGANIZATION_HEADERS),
+            job_title: find(JOB_TITLE_HEADERS),
+            emails,
+            phones,
+            addresses,
+        };
+        if columns.full_name.is_none() && columns.given_name.is_none() && columns.family_name.is_none() {
+            return Err("no name column recognised in the header".to_string());
+        }
+        Ok(columns)
+    }
+
+    fn to_contact(&self, fields: &[String]) -> Result<NewContact, String> {
+        let field = |column: Option<usize>| column.and_then(|i| fields.get(i)).map_or("", |f| f.trim());
+        let label = |source: &LabelSource| match source {
+            // Google marks the primary value's type with a leading "* "
+            LabelSource::Column(column) => field(Some(*column)).trim_start_matches('*').trim().to_string(),
+            LabelSource::Fixed(label) => label.to_string(),
+        };
+        // Google packs several values into one cell separated by " ::: "
+        let values = |columns: &[ValueColumns]| -> Vec<LabelledValue> {
+            columns
+                .iter()
+                .flat_map(|c| {
+                    let label = label(&c.label);
+                    field(Some(c.value))
+                        .split(":::")
+                        .map(str::trim)
+                        .filter(|v| !v.is_empty())
+                        .map(|v| LabelledValue::new(&label, v))
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+
+        let structured_name = StructuredName {
+            prefix: field(self.name_prefix).to_string(),
+            given: field(self.given_name).to_string(),
+            additional: field(self.middle_name).to_string(),
+            family: field(self.family_name).to_string(),
+            suffix: field(self.name_suffix).to_string(),
+        };
+        let name = match field(self.full_name) {
+            "" => structured_name.formatted(),
+            full_name => full_name.to_string(),
+        };
+        if name.is_empty() {
+            return Err("missing name".to_string());
+        }
+
+        let addresses = self
+            .addresses
+            .iter()
+            .map(|a| PostalAddress {
+                label: label(&a.label),
+                street: field(a.street).to_string(),
+                locality: field(a.locality).to_string(),
+                region: field(a.region).to_string(),
+                postal_code: field(a.postal_code).to_string(),
+                country: field(a.country).to_string(),
+            })
+            .filter(|a| !a.is_empty())
+            .collect();
+
+        Ok(NewContact {
+            name,
+            structured_name,
+            emails: values(&self.emails),
+            phones: values(&self.phones),
+            addresses,
+            organization: field(self.organization).to_string(),
+            title: field(self.job_title).to_string(),
+        })
+    }
+}
+
+/// Lowercase a header and drop everything but letters and digits, so that "E-mail Address"
+/// and "email address" compare equal.
+fn normalize_header(header: &str) -> String {
+    header
+        .trim_start_matches('\u{feff}')
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Split CSV text into records per RFC 4180, allowing quoted fields with embedded commas,
+/// quotes and line breaks. A record with an unterminated quoted field is an error.
+fn parse_records(text: &str) -> Vec<Result<Vec<String>, String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() => in_quotes = true,
+            _ if in_quotes => field.push(c),
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                records.push(Ok(std::mem::take(&mut record)));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        records.push(Err("unterminated quoted field".to_string()));
+    } else if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(Ok(record));
+    }
+    records
+}
+
+/// Write a CSV record terminated by CRLF, quoting fields where needed.
+fn write_record<'a>(fields: impl IntoIterator<Item = &'a str>) -> String {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\r', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    format!("{}\r\n", fields.join(","))
+}
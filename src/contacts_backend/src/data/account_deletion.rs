@@ -0,0 +1,37 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+use super::contact::ContactID;
+
+/// Progress of removing a deleted account's contacts, which may take several messages.
+/// Each contact is withdrawn from the users it was shared with as it is removed.
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct AccountDeletion {
+    pub contacts: Vec<ContactID>, // every contact the account owned, sorted
+    pub contacts_removed: u64, // how many of `contacts` have been removed so far
+}
+
+impl AccountDeletion {
+    pub fn new(mut contacts: Vec<ContactID>) -> Self {
+        contacts.sort_unstable();
+        contacts.dedup();
+        Self {
+            contacts,
+            contacts_removed: 0,
+        }
+    }
+}
+
+impl Storable for AccountDeletion {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -3,37 +3,187 @@ use ic_stable_structures::{
     storable::Bound, Storable,
 };
 use std::borrow::Cow;
-use crate::data::counter::Counter;
+use super::labelled_value::LabelledValue;
+use super::new_contact::NewContact;
+use super::postal_address::PostalAddress;
+use super::structured_name::StructuredName;
 
 pub type ContactID = u64;
 
 #[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
 pub struct Contact {
     id: Option<ContactID>,
-    pub name: String,
-    pub email: String,
-    pub phone: String,
+    pub name: String, // display name
+    pub structured_name: StructuredName,
+    pub emails: Vec<LabelledValue>, // the first is the primary email
+    pub phones: Vec<LabelledValue>, // the first is the primary phone
+    pub addresses: Vec<PostalAddress>,
+    pub organization: String,
+    pub title: String, // job title
+    created_at: u64, // nanoseconds since the UNIX epoch
+    updated_at: u64, // nanoseconds since the UNIX epoch
 }
 
 impl Contact {
-    pub fn new(name: String, email: String, phone: String,  contact_id_counter: Option<&mut Counter>) -> Self {
-        match contact_id_counter {
-            Some(counter) => {
-                let id = Some(counter.increment());
-                Self { id, name, email, phone}
-            },
-            None => Self { id: None, name, email, phone}
+    pub fn new(id: ContactID, details: NewContact, now: u64) -> Self {
+        let mut contact = Self {
+            id: Some(id),
+            name: String::new(),
+            structured_name: StructuredName::default(),
+            emails: Vec::new(),
+            phones: Vec::new(),
+            addresses: Vec::new(),
+            organization: String::new(),
+            title: String::new(),
+            created_at: now,
+            updated_at: now,
+        };
+        contact.update(details, now);
+        contact
+    }
+
+    /// Replace the contact's details, bumping its last-modified time.
+    ///
+    /// The display and structured names are each filled in from the other when missing.
+    pub fn update(&mut self, details: NewContact, now: u64) {
+        let mut name = details.name.trim().to_string();
+        let mut structured_name = details.structured_name;
+        if name.is_empty() {
+            name = structured_name.formatted();
+        } else if structured_name.is_empty() {
+            structured_name = StructuredName::from_full_name(&name);
+        }
+
+        self.name = name;
+        self.structured_name = structured_name;
+        self.emails = details.emails;
+        self.phones = details.phones;
+        self.addresses = details.addresses;
+        self.organization = details.organization;
+        self.title = details.title;
+        self.updated_at = now;
+    }
+
+    /// The primary email address, or an empty string if there is none.
+    pub fn primary_email(&self) -> &str {
+        self.emails.first().map_or("", |email| email.value.as_str())
+    }
+
+    /// The primary phone number, or an empty string if there is none.
+    pub fn primary_phone(&self) -> &str {
+        self.phones.first().map_or("", |phone| phone.value.as_str())
+    }
+
+    /// The ID assigned by the canister, if this contact has been stored.
+    pub fn id(&self) -> Option<ContactID> {
+        self.id
+    }
+
+    /// When the contact was created, or 0 if it predates timestamps.
+    #[cfg(test)]
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// When the contact was last modified, or 0 if it predates timestamps.
+    pub fn updated_at(&self) -> u64 {
+        self.updated_at
+    }
+
+    /// Stamp this contact with its canister-assigned ID.
+    pub(crate) fn with_id(self, id: ContactID) -> Self {
+        Self { id: Some(id), ..self }
+    }
+}
+
+/// Contact layout before timestamps were added.
+#[derive(CandidType, Deserialize)]
+struct ContactV1 {
+    id: Option<ContactID>,
+    name: String,
+    email: String,
+    phone: String,
+}
+
+/// Contact layout before multi-valued fields and structured names were added.
+#[derive(CandidType, Deserialize)]
+struct ContactV2 {
+    id: Option<ContactID>,
+    name: String,
+    email: String,
+    phone: String,
+    created_at: u64,
+    updated_at: u64,
+}
+
+impl From<ContactV1> for ContactV2 {
+    fn from(contact: ContactV1) -> Self {
+        Self {
+            id: contact.id,
+            name: contact.name,
+            email: contact.email,
+            phone: contact.phone,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+}
+
+impl From<ContactV2> for Contact {
+    fn from(contact: ContactV2) -> Self {
+        // single values become the primary, unlabelled entry
+        let single = |value: String| match value.trim() {
+            "" => Vec::new(),
+            value => vec![LabelledValue::new("", value)],
+        };
+        Self {
+            id: contact.id,
+            structured_name: StructuredName::from_full_name(&contact.name),
+            name: contact.name,
+            emails: single(contact.email),
+            phones: single(contact.phone),
+            addresses: Vec::new(),
+            organization: String::new(),
+            title: String::new(),
+            created_at: contact.created_at,
+            updated_at: contact.updated_at,
+        }
+    }
+}
+
+/// Versioned envelope for contacts persisted in stable memory.
+///
+/// When `Contact` changes shape, freeze the old layout under a new name, add a variant
+/// for the new layout and migrate the older variants in `into_current`.
+#[derive(CandidType, Deserialize)]
+enum StoredContact {
+    V1(ContactV1),
+    V2(ContactV2),
+    V3(Contact),
+}
+
+impl StoredContact {
+    fn into_current(self) -> Contact {
+        match self {
+            StoredContact::V1(contact) => ContactV2::from(contact).into(),
+            StoredContact::V2(contact) => contact.into(),
+            StoredContact::V3(contact) => contact,
         }
     }
 }
 
 impl Storable for Contact {
     fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+        Cow::Owned(Encode!(&StoredContact::V3(self.clone())).unwrap())
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        let stored = match Decode!(bytes.as_ref(), StoredContact) {
+            Ok(stored) => stored,
+            // records written before the envelope existed are bare V1 contacts
+            Err(_) => StoredContact::V1(Decode!(bytes.as_ref(), ContactV1).unwrap()),
+        };
+        stored.into_current()
     }
 
     const BOUND: Bound = Bound::Unbounded;
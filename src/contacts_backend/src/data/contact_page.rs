@@ -0,0 +1,10 @@
+use candid::{CandidType, Deserialize};
+use super::contact::{Contact, ContactID};
+
+/// A page of contacts, ordered by ascending contact ID.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContactPage {
+    pub contacts: Vec<Contact>,
+    /// Cursor to pass to fetch the next page, or `None` if this is the last page.
+    pub next_cursor: Option<ContactID>,
+}
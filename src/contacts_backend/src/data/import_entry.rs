@@ -0,0 +1,20 @@
+use candid::{CandidType, Deserialize};
+use super::contact::{Contact, ContactID};
+
+/// What happened to a single entry of an import.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub enum ImportOutcome {
+    /// The entry was stored as a new contact.
+    Imported(Box<Contact>),
+    /// The entry matches a contact the user already has, or an earlier entry of the same import.
+    Duplicate(ContactID),
+    /// The entry could not be parsed or failed validation.
+    Rejected(String),
+}
+
+/// Result of importing a single entry, identified by its position in the input (starting at 0).
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportEntry {
+    pub index: u32,
+    pub outcome: ImportOutcome,
+}
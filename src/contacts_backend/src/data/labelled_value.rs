@@ -0,0 +1,18 @@
+use candid::{CandidType, Deserialize};
+
+/// A value such as an email address or phone number, with a free-form label such as
+/// "work", "home" or "mobile". The label may be empty.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LabelledValue {
+    pub label: String,
+    pub value: String,
+}
+
+impl LabelledValue {
+    pub fn new(label: &str, value: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
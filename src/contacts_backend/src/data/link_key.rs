@@ -0,0 +1,33 @@
+use candid::Principal;
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+
+/// Key of the link index and the link request index: an account paired with a principal linked
+/// to it, or asked to link. Keys sort by account first, so all such principals are adjacent.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinkKey {
+    pub account: Principal,
+    pub principal: Principal,
+}
+
+impl Storable for LinkKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        // principals vary in length, so the account is length-prefixed
+        let mut bytes = vec![self.account.as_slice().len() as u8];
+        bytes.extend_from_slice(self.account.as_slice());
+        bytes.extend_from_slice(self.principal.as_slice());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (account, principal) = bytes[1..].split_at(bytes[0] as usize);
+        Self {
+            account: Principal::from_slice(account),
+            principal: Principal::from_slice(principal),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded { max_size: 1 + 29 + 29, is_fixed_size: false };
+}
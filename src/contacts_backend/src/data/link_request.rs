@@ -0,0 +1,31 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+
+/// An offer from an account to link another principal to it, which that principal must
+/// confirm before it expires.
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct LinkRequest {
+    pub account: Principal,
+    pub expires_at: u64, // nanoseconds since the UNIX epoch
+}
+
+impl LinkRequest {
+    pub fn is_active(&self, now: u64) -> bool {
+        now < self.expires_at
+    }
+}
+
+impl Storable for LinkRequest {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
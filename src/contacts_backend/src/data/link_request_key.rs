@@ -0,0 +1,33 @@
+use candid::Principal;
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+
+/// Key of the link requests: a principal paired with an account offering to link it.
+/// Keys sort by principal first, so all offers a principal can confirm are adjacent.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinkRequestKey {
+    pub principal: Principal,
+    pub account: Principal,
+}
+
+impl Storable for LinkRequestKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        // principals vary in length, so the principal is length-prefixed
+        let mut bytes = vec![self.principal.as_slice().len() as u8];
+        bytes.extend_from_slice(self.principal.as_slice());
+        bytes.extend_from_slice(self.account.as_slice());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (principal, account) = bytes[1..].split_at(bytes[0] as usize);
+        Self {
+            principal: Principal::from_slice(principal),
+            account: Principal::from_slice(account),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded { max_size: 1 + 29 + 29, is_fixed_size: false };
+}
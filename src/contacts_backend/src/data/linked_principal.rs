@@ -0,0 +1,9 @@
+use candid::{CandidType, Deserialize, Principal};
+
+/// A principal that signs in to the current user's account.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkedPrincipal {
+    pub principal: Principal,
+    pub linked_at: Option<u64>, // `None` for the principal the account was created with
+    pub is_caller: bool,
+}
@@ -1,4 +1,21 @@
+pub mod account_deletion;
 pub mod contact;
+pub mod contact_page;
+pub mod import_entry;
+pub mod labelled_value;
+pub mod link_key;
+pub mod link_request;
+pub mod link_request_key;
+pub mod linked_principal;
 pub mod user;
 pub mod new_user;
-pub mod counter;
\ No newline at end of file
+pub mod new_contact;
+pub mod postal_address;
+pub mod principal_link;
+pub mod retired_key;
+pub mod retired_username;
+pub mod scored_contact;
+pub mod search_key;
+pub mod share_key;
+pub mod shared_contact;
+pub mod structured_name;
\ No newline at end of file
@@ -0,0 +1,16 @@
+use candid::{CandidType, Deserialize};
+use super::labelled_value::LabelledValue;
+use super::postal_address::PostalAddress;
+use super::structured_name::StructuredName;
+
+/// Contact details supplied by a client. The first email and phone are the primary ones.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct NewContact {
+    pub name: String,
+    pub structured_name: StructuredName,
+    pub emails: Vec<LabelledValue>,
+    pub phones: Vec<LabelledValue>,
+    pub addresses: Vec<PostalAddress>,
+    pub organization: String,
+    pub title: String,
+}
@@ -0,0 +1,20 @@
+use candid::{CandidType, Deserialize};
+
+/// A postal address with a free-form label such as "work" or "home". Any part may be empty.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PostalAddress {
+    pub label: String,
+    pub street: String,
+    pub locality: String, // city
+    pub region: String,   // state or province
+    pub postal_code: String,
+    pub country: String,
+}
+
+impl PostalAddress {
+    pub fn is_empty(&self) -> bool {
+        [&self.street, &self.locality, &self.region, &self.postal_code, &self.country]
+            .iter()
+            .all(|part| part.trim().is_empty())
+    }
+}
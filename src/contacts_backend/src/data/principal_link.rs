@@ -0,0 +1,25 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+
+/// A principal that signs in to another principal's account, such as a second device or
+/// Internet Identity anchor. The account stays keyed by the principal that created it.
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct PrincipalLink {
+    pub account: Principal, // the principal the account was created with
+    pub linked_at: u64, // nanoseconds since the UNIX epoch
+}
+
+impl Storable for PrincipalLink {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -0,0 +1,33 @@
+use candid::Principal;
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+
+/// Key of the retired username index: a principal paired with a username key retired by it.
+/// Keys sort by principal first, so all usernames retired by an account are adjacent.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RetiredKey {
+    pub principal: Principal,
+    pub key: String,
+}
+
+impl Storable for RetiredKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        // principals vary in length, so the principal is length-prefixed
+        let mut bytes = vec![self.principal.as_slice().len() as u8];
+        bytes.extend_from_slice(self.principal.as_slice());
+        bytes.extend_from_slice(self.key.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (principal, key) = bytes[1..].split_at(bytes[0] as usize);
+        Self {
+            principal: Principal::from_slice(principal),
+            key: String::from_utf8(key.to_vec()).unwrap(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -0,0 +1,9 @@
+use candid::{CandidType, Deserialize};
+use super::contact::Contact;
+
+/// A contact matched by a fuzzy search, with a relevance score between 0 and 1.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScoredContact {
+    pub score: f64,
+    pub contact: Contact,
+}
@@ -0,0 +1,32 @@
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+use super::contact::ContactID;
+
+/// Key of the search index: a contact paired with a search term it contains.
+/// Keys sort by contact first, so a contact's terms are adjacent and in order, and a search
+/// only ever reads the terms of the contacts the caller can see.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SearchKey {
+    pub contact_id: ContactID,
+    pub term: String,
+}
+
+impl Storable for SearchKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = self.contact_id.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.term.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (contact_id, term) = bytes.split_at(8);
+        Self {
+            contact_id: ContactID::from_be_bytes(contact_id.try_into().unwrap()),
+            term: String::from_utf8(term.to_vec()).unwrap(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
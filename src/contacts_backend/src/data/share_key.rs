@@ -0,0 +1,32 @@
+use candid::Principal;
+use ic_stable_structures::{
+    storable::Bound, Storable,
+};
+use std::borrow::Cow;
+use super::contact::ContactID;
+
+/// Key of the share index: a contact paired with a user it has been shared with.
+/// Keys sort by contact first, so all recipients of a contact are adjacent.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ShareKey {
+    pub contact_id: ContactID,
+    pub recipient: Principal,
+}
+
+impl Storable for ShareKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = self.contact_id.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.recipient.as_slice());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (contact_id, recipient) = bytes.split_at(8);
+        Self {
+            contact_id: ContactID::from_be_bytes(contact_id.try_into().unwrap()),
+            recipient: Principal::from_slice(recipient),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded { max_size: 8 + 29, is_fixed_size: false };
+}
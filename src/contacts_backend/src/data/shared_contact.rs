@@ -0,0 +1,9 @@
+use candid::{CandidType, Deserialize};
+use super::contact::Contact;
+
+/// A contact shared with the current user, along with the username of its owner.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct SharedContact {
+    pub owner: String,
+    pub contact: Contact,
+}
@@ -0,0 +1,41 @@
+use candid::{CandidType, Deserialize};
+
+/// A name split into its parts, as in the vCard `N` property.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct StructuredName {
+    pub prefix: String,     // e.g. "Dr."
+    pub given: String,      // first name
+    pub additional: String, // middle names
+    pub family: String,     // last name
+    pub suffix: String,     // e.g. "Jr."
+}
+
+impl StructuredName {
+    /// Split a full name into given and family parts on the last space. A single-word name
+    /// is treated as a given name.
+    pub fn from_full_name(name: &str) -> Self {
+        let (given, family) = match name.trim().rsplit_once(' ') {
+            Some((given, family)) => (given.trim(), family),
+            None => (name.trim(), ""),
+        };
+        Self {
+            given: given.to_string(),
+            family: family.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The parts joined in display order, e.g. "Dr. Jane Q Roe Jr.".
+    pub fn formatted(&self) -> String {
+        [&self.prefix, &self.given, &self.additional, &self.family, &self.suffix]
+            .iter()
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formatted().is_empty()
+    }
+}
@@ -11,15 +11,53 @@ pub struct User {
     pub username: String,
     pub contacts: Vec<ContactID>, // Contact IDs
     pub shared_contacts: Vec<ContactID>, // Contact IDs
+    pub default_country: Option<String>, // ISO 3166-1 alpha-2 code for phone numbers without one
+}
+
+/// User layout before the default phone country was added.
+#[derive(CandidType, Deserialize)]
+struct UserV1 {
+    username: String,
+    contacts: Vec<ContactID>,
+    shared_contacts: Vec<ContactID>,
+}
+
+/// Versioned envelope for users persisted in stable memory.
+///
+/// When `User` changes shape, freeze the old layout under a new name, add a variant
+/// for the new layout and migrate the older variants in `into_current`.
+#[derive(CandidType, Deserialize)]
+enum StoredUser {
+    V1(UserV1),
+    V2(User),
+}
+
+impl StoredUser {
+    fn into_current(self) -> User {
+        match self {
+            StoredUser::V1(user) => User {
+                username: user.username,
+                contacts: user.contacts,
+                shared_contacts: user.shared_contacts,
+                default_country: None,
+            },
+            StoredUser::V2(user) => user,
+        }
+    }
 }
 
 impl Storable for User {
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+        Cow::Owned(Encode!(&StoredUser::V2(self.clone())).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        let stored = match Decode!(bytes.as_ref(), StoredUser) {
+            Ok(stored) => stored,
+            // records written before the envelope existed are bare V1 users
+            Err(_) => StoredUser::V1(Decode!(bytes.as_ref(), UserV1).unwrap()),
+        };
+        stored.into_current()
     }
 
     const BOUND: Bound = Bound::Unbounded;
@@ -0,0 +1,57 @@
+/// Largest encoded arguments accepted by ingress, in bytes. Most endpoints take IDs, usernames
+/// or short queries; contact details are bounded by `validation::validate_contact`, and imports
+/// by `MAX_IMPORT_ENTRIES` cards of a few kilobytes each.
+const MAX_ARG_BYTES: usize = 1024;
+const MAX_CONTACT_ARG_BYTES: usize = 64 * 1024;
+const MAX_IMPORT_ARG_BYTES: usize = 1024 * 1024;
+
+/// An endpoint that may be called by ingress message.
+pub struct IngressMethod {
+    pub name: &'static str,
+    /// Whether the caller must have an account, or be linked to one.
+    pub requires_account: bool,
+    pub max_arg_bytes: usize,
+}
+
+const fn method(name: &'static str, requires_account: bool, max_arg_bytes: usize) -> IngressMethod {
+    IngressMethod { name, requires_account, max_arg_bytes }
+}
+
+/// Every endpoint of the canister. Queries are listed too, since they can also be called as
+/// updates.
+const INGRESS_METHODS: &[IngressMethod] = &[
+    method("whoami", false, MAX_ARG_BYTES),
+    method("create_account", false, MAX_ARG_BYTES),
+    method("change_username", true, MAX_ARG_BYTES),
+    method("delete_account", true, MAX_ARG_BYTES),
+    method("request_link", true, MAX_ARG_BYTES),
+    method("confirm_link", false, MAX_ARG_BYTES),
+    method("get_linked_principals", true, MAX_ARG_BYTES),
+    method("unlink_principal", true, MAX_ARG_BYTES),
+    method("get_default_country", true, MAX_ARG_BYTES),
+    method("set_default_country", true, MAX_ARG_BYTES),
+    method("create_contact", true, MAX_CONTACT_ARG_BYTES),
+    method("get_contacts", true, MAX_ARG_BYTES),
+    method("get_contacts_page", true, MAX_ARG_BYTES),
+    method("get_recently_changed_contacts", true, MAX_ARG_BYTES),
+    method("search_contacts", true, MAX_ARG_BYTES),
+    method("fuzzy_search_contacts", true, MAX_ARG_BYTES),
+    method("export_vcard", true, MAX_ARG_BYTES),
+    method("export_address_book_vcard", true, MAX_ARG_BYTES),
+    method("export_jcard", true, MAX_ARG_BYTES),
+    method("export_address_book_jcard", true, MAX_ARG_BYTES),
+    method("export_csv", true, MAX_ARG_BYTES),
+    method("get_shared_contacts", true, MAX_ARG_BYTES),
+    method("import_vcards", true, MAX_IMPORT_ARG_BYTES),
+    method("import_jcards", true, MAX_IMPORT_ARG_BYTES),
+    method("import_csv", true, MAX_IMPORT_ARG_BYTES),
+    method("edit_contact", true, MAX_CONTACT_ARG_BYTES),
+    method("delete_contact", true, MAX_ARG_BYTES),
+    method("share_contact", true, MAX_ARG_BYTES),
+    method("revoke_shared_contact", true, MAX_ARG_BYTES),
+];
+
+/// Look up an endpoint by name.
+pub fn ingress_method(name: &str) -> Option<&'static IngressMethod> {
+    INGRESS_METHODS.iter().find(|method| method.name == name)
+}
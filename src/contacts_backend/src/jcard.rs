@@ -0,0 +1,195 @@
+use serde_json::{json, Value};
+
+use crate::data::contact::Contact;
+use crate::data::labelled_value::LabelledValue;
+use crate::data::new_contact::NewContact;
+use crate::data::postal_address::PostalAddress;
+use crate::data::structured_name::StructuredName;
+use crate::datetime;
+
+/// Serialize a contact as a jCard (RFC 7095), the JSON form of a vCard 4.0.
+pub fn to_jcard(contact: &Contact) -> Value {
+    let name = &contact.structured_name;
+    let mut properties = vec![
+        json!(["version", {}, "text", "4.0"]),
+        json!(["fn", {}, "text", contact.name]),
+        json!(["n", {}, "text", [name.family, name.given, name.additional, name.prefix, name.suffix]]),
+    ];
+    if !contact.organization.is_empty() {
+        properties.push(json!(["org", {}, "text", contact.organization]));
+    }
+    if !contact.title.is_empty() {
+        properties.push(json!(["title", {}, "text", contact.title]));
+    }
+    for email in contact.emails.iter().filter(|e| !e.value.is_empty()) {
+        properties.push(json!(["email", type_param(&email.label), "text", email.value]));
+    }
+    for phone in contact.phones.iter().filter(|p| !p.value.is_empty()) {
+        properties.push(json!(["tel", type_param(&phone.label), "text", phone.value]));
+    }
+    for address in contact.addresses.iter().filter(|a| !a.is_empty()) {
+        // adr is [pobox, extended, street, locality, region, postal-code, country]
+        properties.push(json!([
+            "adr",
+            type_param(&address.label),
+            "text",
+            ["", "", address.street, address.locality, address.region, address.postal_code, address.country]
+        ]));
+    }
+    if contact.updated_at() > 0 {
+        properties.push(json!(["rev", {}, "timestamp", datetime::format_utc(contact.updated_at(), true)]));
+    }
+    json!(["vcard", properties])
+}
+
+/// The parameters object for a label. vCard calls a mobile number a "cell" phone.
+fn type_param(label: &str) -> Value {
+    match label.trim().to_lowercase().as_str() {
+        "" => json!({}),
+        "mobile" => json!({ "type": "cell" }),
+        label => json!({ "type": label }),
+    }
+}
+
+/// Parse JSON holding either a single jCard or an array of jCards, in order.
+///
+/// Each jCard yields either the contact details it holds or the reason it could not be
+/// parsed. The name comes from `fn`, falling back to `n`. Every `email`, `tel` and `adr` is
+/// kept in order, labelled with its first meaningful `type`. `org` and `title` are read as
+/// well; other properties are ignored.
+pub fn parse_jcards(text: &str) -> Result<Vec<Result<NewContact, String>>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+    if value.get(0).and_then(Value::as_str) == Some("vcard") {
+        return Ok(vec![jcard_to_contact(&value)]);
+    }
+    match value.as_array() {
+        Some(jcards) => Ok(jcards.iter().map(jcard_to_contact).collect()),
+        None => Err("expected a jCard or an array of jCards".to_string()),
+    }
+}
+
+fn jcard_to_contact(jcard: &Value) -> Result<NewContact, String> {
+    if jcard.get(0).and_then(Value::as_str) != Some("vcard") {
+        return Err("not a jCard".to_string());
+    }
+    let properties = jcard
+        .get(1)
+        .and_then(Value::as_array)
+        .ok_or("missing property list")?;
+
+    // a property is [name, parameters, type, value...]
+    let all = |name: &'static str| {
+        properties.iter().filter_map(move |property| {
+            let matches = property.get(0)?.as_str()?.eq_ignore_ascii_case(name);
+            let value = property.get(3)?;
+            (matches && !is_blank(value)).then(|| (label_from_params(property.get(1)), value))
+        })
+    };
+    let first = |name: &'static str| all(name).next().map(|(_, value)| value);
+    // structured values are arrays of components
+    let component = |value: &Value, i: usize| {
+        value.get(i).map(|c| text_value(c).trim().to_string()).unwrap_or_default()
+    };
+
+    // n is [family, given, additional, prefixes, suffixes]
+    let structured_name = first("n")
+        .map(|n| StructuredName {
+            prefix: component(n, 3),
+            given: component(n, 1),
+            additional: component(n, 2),
+            family: component(n, 0),
+            suffix: component(n, 4),
+        })
+        .unwrap_or_default();
+    let name = match first("fn") {
+        Some(full_name) => text_value(full_name).trim().to_string(),
+        None => structured_name.formatted(),
+    };
+    if name.is_empty() {
+        return Err("missing fn or n".to_string());
+    }
+
+    let emails = all("email")
+        .map(|(label, email)| LabelledValue::new(&label, text_value(email).trim()))
+        .collect();
+    let phones = all("tel")
+        .map(|(label, tel)| {
+            let tel = text_value(tel);
+            // numbers are often `tel:` URIs
+            let tel = tel.strip_prefix("tel:").unwrap_or(&tel);
+            LabelledValue::new(&label, tel.trim())
+        })
+        .collect();
+    // adr is [pobox, extended, street, locality, region, postal-code, country]
+    let addresses = all("adr")
+        .map(|(label, adr)| PostalAddress {
+            label,
+            street: [component(adr, 0), component(adr, 1), component(adr, 2)]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            locality: component(adr, 3),
+            region: component(adr, 4),
+            postal_code: component(adr, 5),
+            country: component(adr, 6),
+        })
+        .filter(|a| !a.is_empty())
+        .collect();
+    // org may be [organization, unit, ...], only the organization is kept
+    let organization = first("org")
+        .map(|org| match org {
+            Value::Array(_) => component(org, 0),
+            _ => text_value(org).trim().to_string(),
+        })
+        .unwrap_or_default();
+    let title = first("title").map(|t| text_value(t).trim().to_string()).unwrap_or_default();
+
+    Ok(NewContact {
+        name,
+        structured_name,
+        emails,
+        phones,
+        addresses,
+        organization,
+        title,
+    })
+}
+
+/// Pick a label from a property's `type` parameter, which may be a string or an array,
+/// skipping values that describe the kind of value rather than its use. A "cell" phone is
+/// labelled "mobile".
+fn label_from_params(params: Option<&Value>) -> String {
+    const NOT_LABELS: [&str; 4] = ["pref", "voice", "text", "internet"];
+    let types = match params.and_then(|p| p.get("type")) {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(ts)) => ts.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    types
+        .iter()
+        .flat_map(|t| t.split(','))
+        .map(|t| t.trim().to_lowercase())
+        .find(|t| !t.is_empty() && !NOT_LABELS.contains(&t.as_str()))
+        .map(|t| if t == "cell" { "mobile".to_string() } else { t })
+        .unwrap_or_default()
+}
+
+/// Flatten a property value to text; structured components are joined with spaces.
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(text_value)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn is_blank(value: &Value) -> bool {
+    text_value(value).trim().is_empty()
+}
@@ -1,18 +1,45 @@
+mod csv;
 mod data;
+mod datetime;
+mod ingress;
+mod jcard;
 mod response;
+mod search;
+mod username;
+mod validation;
+mod vcard;
 
+use data::new_contact::NewContact;
 use data::new_user::NewUser;
-use ic_cdk::{api, query, update};
+use ic_cdk::{api, init, inspect_message, post_upgrade, query, update};
 
+use data::account_deletion::AccountDeletion;
 use data::contact::{Contact, ContactID};
+use data::contact_page::ContactPage;
+use data::import_entry::{ImportEntry, ImportOutcome};
+use data::link_key::LinkKey;
+use data::link_request::LinkRequest;
+use data::link_request_key::LinkRequestKey;
+use data::linked_principal::LinkedPrincipal;
+use data::principal_link::PrincipalLink;
+use data::retired_key::RetiredKey;
+use data::retired_username::RetiredUsername;
+use data::scored_contact::ScoredContact;
+use data::search_key::SearchKey;
+use data::share_key::ShareKey;
+use data::shared_contact::SharedContact;
 use data::user::User;
-use response::httpish;
+use csv::CsvLayout;
+use response::error::{ContactsError, ContactsResult};
+use vcard::VCardVersion;
 
 // Data Structures
 use candid::Principal;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, };
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
 
 mod tests; 
 
@@ -39,24 +66,787 @@ thread_local! {
         )
     );
 
-    // Initialize a `StableBTreeMap` with `MemoryId(2)` for usernames to principal mappings.
+    // Initialize a `StableBTreeMap` with `MemoryId(2)` for case-folded usernames to principal mappings.
     static USERNAME_MAP: RefCell<StableBTreeMap<String, Principal, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
         )
     );
 
+    // Initialize a `StableCell` with `MemoryId(3)` holding the next unassigned contact ID.
+    static NEXT_CONTACT_ID: RefCell<StableCell<ContactID, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
+            0,
+        ).expect("Failed to initialize the contact ID counter")
+    );
+
+    // Initialize a `StableCell` with `MemoryId(4)` holding the schema version of the stored records.
+    static SCHEMA_VERSION: RefCell<StableCell<u32, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
+            0,
+        ).expect("Failed to initialize the schema version")
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(5)` for the contact search index.
+    static SEARCH_INDEX: RefCell<StableBTreeMap<SearchKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(6)` for case-folded usernames given up by
+    // `change_username` that are still reserved for their former owner.
+    static RETIRED_USERNAME_MAP: RefCell<StableBTreeMap<String, RetiredUsername, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(7)` for deleted accounts whose data is
+    // still being removed.
+    static PENDING_DELETIONS: RefCell<StableBTreeMap<Principal, AccountDeletion, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(8)` for principals linked to another
+    // principal's account.
+    static PRINCIPAL_LINK_MAP: RefCell<StableBTreeMap<Principal, PrincipalLink, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(9)` for link requests waiting for the
+    // principal they name to confirm them.
+    static LINK_REQUEST_MAP: RefCell<StableBTreeMap<LinkRequestKey, LinkRequest, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(10)` for the owner of each contact.
+    static CONTACT_OWNER_MAP: RefCell<StableBTreeMap<ContactID, Principal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(11)` for the users each contact is shared with.
+    static SHARE_INDEX: RefCell<StableBTreeMap<ShareKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(12)` for the principals linked to each account.
+    static LINK_INDEX: RefCell<StableBTreeMap<LinkKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(13)` for the usernames each account has
+    // retired.
+    static RETIRED_USERNAME_INDEX: RefCell<StableBTreeMap<RetiredKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))),
+        )
+    );
+
+    // Initialize a `StableBTreeMap` with `MemoryId(14)` for the principals each account has
+    // pending link requests for.
+    static LINK_REQUEST_INDEX: RefCell<StableBTreeMap<LinkKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))),
+        )
+    );
+
+}
+
+/// Schema version from which `USERNAME_MAP` is keyed by `username::username_key`.
+const CASE_FOLDED_USERNAMES_VERSION: u32 = 6;
+
+/// Schema version from which `LINK_REQUEST_MAP` is keyed by `LinkRequestKey`.
+const KEYED_LINK_REQUESTS_VERSION: u32 = 12;
+
+/// Schema version written by this build. Bump it whenever `User` or `Contact` gains a new
+/// stored variant or a derived index changes, so existing records are rewritten and the
+/// indexes rebuilt on the next upgrade.
+const CURRENT_SCHEMA_VERSION: u32 = 12;
+
+/// Largest page that `get_contacts_page` will return, keeping replies well under the
+/// IC response size limit.
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Most entries a single import call will process, keeping it well under the instruction limit.
+const MAX_IMPORT_ENTRIES: usize = 500;
+
+/// How long `change_username` keeps an old username reserved for its former owner when
+/// asked to: 30 days, in nanoseconds.
+const USERNAME_GRACE_PERIOD: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Contacts removed per message when deleting an account, keeping each message well under
+/// the instruction limit however large the address book is.
+const DELETION_CONTACTS_PER_MESSAGE: usize = 100;
+
+/// How long a principal has to confirm a `request_link`: 1 hour, in nanoseconds.
+const LINK_REQUEST_TTL: u64 = 60 * 60 * 1_000_000_000;
+
+/// Most principals that can be linked to one account, besides the one that created it.
+const MAX_LINKED_PRINCIPALS: usize = 10;
+
+/// Most link requests one account can have waiting for confirmation at a time.
+const MAX_PENDING_LINK_REQUESTS: usize = 10;
+
+// Lifecycle Hooks
+#[init]
+fn init() {
+    set_schema_version(CURRENT_SCHEMA_VERSION);
+}
+
+#[post_upgrade]
+fn post_upgrade() {
+    migrate_stable_data();
+    // timers do not survive upgrades
+    if !PENDING_DELETIONS.with(|p| p.borrow().is_empty()) {
+        schedule_account_deletions();
+    }
+}
+
+/// Rewrite every stored record in the current layout if it was written by an older schema.
+/// Records are also migrated lazily on read, so this only saves the decode fallback from
+/// running on every access.
+fn migrate_stable_data() {
+    let stored_version = SCHEMA_VERSION.with(|p| *p.borrow().get());
+    if stored_version >= CURRENT_SCHEMA_VERSION {
+        return;
+    }
+    ic_cdk::println!(
+        "/post_upgrade [INFO] - Migrating stable data from schema v{} to v{}",
+        stored_version,
+        CURRENT_SCHEMA_VERSION
+    );
+
+    USER_MAP.with(|p| {
+        let mut users = p.borrow_mut();
+        let entries: Vec<(Principal, User)> = users.iter().collect();
+        for (principal, user) in entries {
+            users.insert(principal, user);
+        }
+    });
+    CONTACT_MAP.with(|p| {
+        let mut contacts = p.borrow_mut();
+        let entries: Vec<(ContactID, Contact)> = contacts.iter().collect();
+        for (id, contact) in entries {
+            contacts.insert(id, contact);
+        }
+    });
+    rebuild_search_index();
+    // later keys are assigned by create_account and change_username, not derived from users
+    if stored_version < CASE_FOLDED_USERNAMES_VERSION {
+        rebuild_username_map();
+    }
+    rebuild_contact_owners();
+    rebuild_share_index();
+    rebuild_link_index();
+    rebuild_retired_username_index();
+    // requests were keyed by principal alone and cannot be decoded as keys any more; they
+    // expire within `LINK_REQUEST_TTL` anyway, so they are dropped rather than converted
+    if stored_version < KEYED_LINK_REQUESTS_VERSION {
+        LINK_REQUEST_MAP.with(|p| p.borrow_mut().clear_new());
+    }
+    rebuild_link_request_index();
+
+    set_schema_version(CURRENT_SCHEMA_VERSION);
+    ic_cdk::println!("/post_upgrade [DONE] - Stable data migrated");
+}
+
+fn set_schema_version(version: u32) {
+    SCHEMA_VERSION.with(|p| {
+        p.borrow_mut()
+            .set(version)
+            .expect("Failed to update the schema version")
+    });
+}
+
+/// Drop ingress messages that would only be rejected before they are executed, so they cost
+/// no cycles: calls from the anonymous principal, to unknown methods, from callers without an
+/// account to methods that need one, and with oversized arguments. Endpoints still check the
+/// caller themselves, since queries and inter-canister calls skip this hook.
+#[inspect_message]
+fn inspect_message() {
+    match inspect_ingress(&api::call::method_name(), api::call::arg_data_raw_size()) {
+        Ok(()) => api::call::accept_message(),
+        Err(reason) => ic_cdk::println!("/inspect_message [REJECT] - {}", reason),
+    }
+}
+
+/// Check an ingress message against `ingress::ingress_method`, returning why it is dropped.
+fn inspect_ingress(method_name: &str, arg_bytes: usize) -> Result<(), String> {
+    let Some(method) = ingress::ingress_method(method_name) else {
+        return Err(format!("Unknown method {}", method_name));
+    };
+    if arg_bytes > method.max_arg_bytes {
+        return Err(format!(
+            "Arguments to {} are {} bytes, over the limit of {}",
+            method_name, arg_bytes, method.max_arg_bytes
+        ));
+    }
+    let user_id = get_user_id().map_err(|_| "Anonymous caller".to_string())?;
+    if method.requires_account && !USER_MAP.with(|p| p.borrow().contains_key(&user_id)) {
+        return Err(format!("Caller has no account for {}", method_name));
+    }
+    Ok(())
 }
 
 // Helper Functions
-fn get_user_id() -> Principal {
-    api::caller()
+/// The caller, unless it is the anonymous principal, which every unauthenticated visitor
+/// shares and so must never own data.
+fn get_caller() -> ContactsResult<Principal> {
+    let caller = api::caller();
+    if caller == Principal::anonymous() {
+        ic_cdk::println!("[REJECT] - Anonymous caller");
+        return Err(ContactsError::AnonymousCaller);
+    }
+    Ok(caller)
+}
+
+/// The account the caller acts on: the caller itself, or the account it is linked to.
+fn get_user_id() -> ContactsResult<Principal> {
+    let caller = get_caller()?;
+    Ok(PRINCIPAL_LINK_MAP.with(|p| p.borrow().get(&caller)).map_or(caller, |link| link.account))
+}
+
+/// Principals linked to an account, with when they were linked.
+fn linked_principals(account: Principal) -> Vec<(Principal, PrincipalLink)> {
+    let principals: Vec<Principal> = LINK_INDEX.with(|p| {
+        p.borrow()
+            .range(LinkKey { account, principal: Principal::management_canister() }..)
+            .take_while(|(key, _)| key.account == account)
+            .map(|(key, _)| key.principal)
+            .collect()
+    });
+    principals
+        .into_iter()
+        .filter_map(|principal| Some((principal, PRINCIPAL_LINK_MAP.with(|p| p.borrow().get(&principal))?)))
+        .collect()
+}
+
+/// Link `principal` to `account`, or unlink it with `None`, keeping `LINK_INDEX` in step.
+fn set_principal_link(principal: Principal, link: Option<PrincipalLink>) {
+    let previous = PRINCIPAL_LINK_MAP.with(|p| p.borrow_mut().remove(&principal));
+    if let Some(previous) = previous {
+        LINK_INDEX.with(|p| p.borrow_mut().remove(&LinkKey { account: previous.account, principal }));
+    }
+    if let Some(link) = link {
+        LINK_INDEX.with(|p| p.borrow_mut().insert(LinkKey { account: link.account, principal }, ()));
+        PRINCIPAL_LINK_MAP.with(|p| p.borrow_mut().insert(principal, link));
+    }
+}
+
+/// The link requests pending for a principal that are still active and whose account still
+/// exists. Lapsed requests are dropped along the way.
+fn pending_link_requests(principal: Principal) -> Vec<LinkRequest> {
+    let requests: Vec<LinkRequest> = LINK_REQUEST_MAP.with(|p| {
+        p.borrow()
+            .range(LinkRequestKey { principal, account: Principal::management_canister() }..)
+            .take_while(|(key, _)| key.principal == principal)
+            .map(|(_, request)| request)
+            .collect()
+    });
+    let now = api::time();
+    let (active, lapsed): (Vec<_>, Vec<_>) = requests.into_iter().partition(|request| {
+        request.is_active(now) && USER_MAP.with(|p| p.borrow().contains_key(&request.account))
+    });
+    for request in lapsed {
+        set_link_request(principal, request.account, None);
+    }
+    active
+}
+
+/// Principals an account has link requests for, whether or not they have expired.
+fn requested_principals(account: Principal) -> Vec<Principal> {
+    LINK_REQUEST_INDEX.with(|p| {
+        p.borrow()
+            .range(LinkKey { account, principal: Principal::management_canister() }..)
+            .take_while(|(key, _)| key.account == account)
+            .map(|(key, _)| key.principal)
+            .collect()
+    })
+}
+
+/// Record `account`'s request to link `principal`, or withdraw it with `None`, keeping
+/// `LINK_REQUEST_INDEX` in step.
+fn set_link_request(principal: Principal, account: Principal, request: Option<LinkRequest>) {
+    let key = LinkRequestKey { principal, account };
+    match request {
+        Some(request) => {
+            LINK_REQUEST_INDEX.with(|p| p.borrow_mut().insert(LinkKey { account, principal }, ()));
+            LINK_REQUEST_MAP.with(|p| p.borrow_mut().insert(key, request));
+        }
+        None => {
+            LINK_REQUEST_INDEX.with(|p| p.borrow_mut().remove(&LinkKey { account, principal }));
+            LINK_REQUEST_MAP.with(|p| p.borrow_mut().remove(&key));
+        }
+    }
+}
+
+/// Drop an account's expired link requests, returning how many are still pending.
+fn prune_link_requests(account: Principal, now: u64) -> usize {
+    let mut pending = 0;
+    for principal in requested_principals(account) {
+        let request = LINK_REQUEST_MAP.with(|p| p.borrow().get(&LinkRequestKey { principal, account }));
+        if request.is_some_and(|request| request.is_active(now)) {
+            pending += 1;
+        } else {
+            set_link_request(principal, account, None);
+        }
+    }
+    pending
+}
+
+/// Allocate a new contact ID. IDs are never reused, even after the contact is deleted.
+fn next_contact_id() -> ContactID {
+    // contacts created before the counter existed were keyed by insertion order,
+    // so never hand out an ID at or below the highest one already stored
+    let stored_next = CONTACT_MAP.with(|p| p.borrow().last_key_value().map_or(0, |(id, _)| id + 1));
+    NEXT_CONTACT_ID.with(|p| {
+        let mut cell = p.borrow_mut();
+        let id = (*cell.get()).max(stored_next);
+        cell.set(id + 1).expect("Failed to update the contact ID counter");
+        id
+    })
+}
+
+/// Add a contact's terms to the search index.
+fn index_contact(contact_id: ContactID, contact: &Contact) {
+    SEARCH_INDEX.with(|p| {
+        let mut index = p.borrow_mut();
+        for term in search::index_terms(contact) {
+            index.insert(SearchKey { contact_id, term }, ());
+        }
+    });
+}
+
+/// Remove a contact's terms from the search index.
+fn unindex_contact(contact_id: ContactID, contact: &Contact) {
+    SEARCH_INDEX.with(|p| {
+        let mut index = p.borrow_mut();
+        for term in search::index_terms(contact) {
+            index.remove(&SearchKey { contact_id, term });
+        }
+    });
+}
+
+/// Rebuild the search index from scratch out of every stored contact.
+fn rebuild_search_index() {
+    // cleared without decoding, as keys written by older schemas may be laid out differently
+    SEARCH_INDEX.with(|p| p.borrow_mut().clear_new());
+    let contacts: Vec<(ContactID, Contact)> = CONTACT_MAP.with(|p| p.borrow().iter().collect());
+    for (id, contact) in contacts {
+        index_contact(id, &contact);
+    }
+}
+
+/// Rebuild `CONTACT_OWNER_MAP` from scratch out of every user's contacts.
+fn rebuild_contact_owners() {
+    CONTACT_OWNER_MAP.with(|p| {
+        let mut owners = p.borrow_mut();
+        let ids: Vec<ContactID> = owners.iter().map(|(id, _)| id).collect();
+        for id in ids {
+            owners.remove(&id);
+        }
+        USER_MAP.with(|users| {
+            for (principal, user) in users.borrow().iter() {
+                for contact_id in user.contacts {
+                    owners.insert(contact_id, principal);
+                }
+            }
+        });
+    });
+}
+
+/// Rebuild `LINK_INDEX` from scratch out of every principal link.
+fn rebuild_link_index() {
+    LINK_INDEX.with(|p| {
+        let mut index = p.borrow_mut();
+        index.clear_new();
+        PRINCIPAL_LINK_MAP.with(|links| {
+            for (principal, link) in links.borrow().iter() {
+                index.insert(LinkKey { account: link.account, principal }, ());
+            }
+        });
+    });
+}
+
+/// Rebuild `LINK_REQUEST_INDEX` from scratch out of every link request.
+fn rebuild_link_request_index() {
+    LINK_REQUEST_INDEX.with(|p| {
+        let mut index = p.borrow_mut();
+        index.clear_new();
+        LINK_REQUEST_MAP.with(|requests| {
+            for (key, _) in requests.borrow().iter() {
+                index.insert(LinkKey { account: key.account, principal: key.principal }, ());
+            }
+        });
+    });
+}
+
+/// Rebuild `RETIRED_USERNAME_INDEX` from scratch out of every retired username, dropping
+/// expired reservations along the way.
+fn rebuild_retired_username_index() {
+    let now = api::time();
+    let retired: Vec<(String, RetiredUsername)> = RETIRED_USERNAME_MAP.with(|p| p.borrow().iter().collect());
+    RETIRED_USERNAME_INDEX.with(|p| p.borrow_mut().clear_new());
+    for (key, reservation) in retired {
+        if reservation.is_active(now) {
+            RETIRED_USERNAME_INDEX.with(|p| {
+                p.borrow_mut().insert(RetiredKey { principal: reservation.principal, key }, ())
+            });
+        } else {
+            RETIRED_USERNAME_MAP.with(|p| p.borrow_mut().remove(&key));
+        }
+    }
+}
+
+/// Rebuild `SHARE_INDEX` from scratch out of every user's shared contacts.
+fn rebuild_share_index() {
+    SHARE_INDEX.with(|p| {
+        let mut index = p.borrow_mut();
+        let keys: Vec<ShareKey> = index.iter().map(|(k, _)| k).collect();
+        for key in keys {
+            index.remove(&key);
+        }
+        USER_MAP.with(|users| {
+            for (principal, user) in users.borrow().iter() {
+                for contact_id in user.shared_contacts {
+                    index.insert(ShareKey { contact_id, recipient: principal }, ());
+                }
+            }
+        });
+    });
+}
+
+/// Users a contact has been shared with.
+fn contact_recipients(contact_id: ContactID) -> Vec<Principal> {
+    SHARE_INDEX.with(|p| {
+        p.borrow()
+            .range(ShareKey { contact_id, recipient: Principal::management_canister() }..)
+            .take_while(|(key, _)| key.contact_id == contact_id)
+            .map(|(key, _)| key.recipient)
+            .collect()
+    })
+}
+
+/// Withdraw a contact from every user it has been shared with.
+fn unshare_contact(contact_id: ContactID) {
+    for recipient_id in contact_recipients(contact_id) {
+        if let Some(mut recipient) = USER_MAP.with(|p| p.borrow().get(&recipient_id)) {
+            recipient.shared_contacts.retain(|&id| id != contact_id);
+            USER_MAP.with(|p| p.borrow_mut().insert(recipient_id, recipient));
+        }
+        SHARE_INDEX.with(|p| p.borrow_mut().remove(&ShareKey { contact_id, recipient: recipient_id }));
+    }
+}
+
+/// Whether a contact has an indexed term starting with `prefix`. Its terms are sorted, so
+/// only the first one at or after `prefix` needs checking.
+fn contact_has_term_prefix(contact_id: ContactID, prefix: &str) -> bool {
+    SEARCH_INDEX.with(|p| {
+        p.borrow()
+            .range(SearchKey { contact_id, term: prefix.to_string() }..)
+            .next()
+            .is_some_and(|(key, _)| key.contact_id == contact_id && key.term.starts_with(prefix))
+    })
+}
+
+/// Store a new contact owned by `user`, the account of `owner`, assigning its ID and indexing
+/// it for search. The caller is responsible for persisting the updated `user`.
+fn store_contact(owner: Principal, user: &mut User, details: NewContact) -> Contact {
+    let contact_id = next_contact_id();
+    let contact = Contact::new(contact_id, details, api::time());
+    CONTACT_MAP.with(|p| p.borrow_mut().insert(contact_id, contact.clone()));
+    CONTACT_OWNER_MAP.with(|p| p.borrow_mut().insert(contact_id, owner));
+    index_contact(contact_id, &contact);
+    user.contacts.push(contact_id);
+    contact
+}
+
+/// Key under which two contacts are considered duplicates: case-insensitive name and primary
+/// email, and the digits of the primary phone number.
+fn duplicate_key(name: &str, email: &str, phone: &str) -> (String, String, String) {
+    (
+        name.trim().to_lowercase(),
+        email.trim().to_lowercase(),
+        phone.chars().filter(|c| c.is_ascii_digit()).collect(),
+    )
+}
+
+/// Store parsed import entries as contacts owned by `user`, the account of `owner`, rejecting
+/// entries that fail validation and skipping duplicates of existing contacts and of earlier
+/// entries. The caller is responsible for persisting the updated `user`.
+fn import_entries(owner: Principal, user: &mut User, entries: Vec<Result<NewContact, String>>) -> Vec<ImportEntry> {
+    let default_country = user.default_country.clone();
+    let mut existing: BTreeMap<(String, String, String), ContactID> = load_contacts(&user.contacts)
+        .iter()
+        .filter_map(|c| Some((duplicate_key(&c.name, c.primary_email(), c.primary_phone()), c.id()?)))
+        .collect();
+
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let entry = entry.and_then(|details| {
+                validation::validate_contact(details, default_country.as_deref()).map_err(|error| match error {
+                    ContactsError::InvalidInput { field, reason } => format!("{} {}", field, reason),
+                    other => format!("{:?}", other),
+                })
+            });
+            let outcome = match entry {
+                Err(reason) => ImportOutcome::Rejected(reason),
+                Ok(details) => {
+                    let name = match details.name.trim() {
+                        "" => details.structured_name.formatted(),
+                        name => name.to_string(),
+                    };
+                    let key = duplicate_key(
+                        &name,
+                        details.emails.first().map_or("", |email| email.value.as_str()),
+                        details.phones.first().map_or("", |phone| phone.value.as_str()),
+                    );
+                    match existing.get(&key) {
+                        Some(&existing_id) => ImportOutcome::Duplicate(existing_id),
+                        None => {
+                            let contact = store_contact(owner, user, details);
+                            existing.insert(key, contact.id().unwrap());
+                            ImportOutcome::Imported(Box::new(contact))
+                        }
+                    }
+                }
+            };
+            ImportEntry { index: index as u32, outcome }
+        })
+        .collect()
+}
+
+/// Find the account with a username, ignoring case and Unicode compatibility differences.
+/// Usernames retired with a grace period still resolve to their former owner until it ends.
+fn principal_for_username(username: &str) -> Option<Principal> {
+    let key = username::username_key(username);
+    USERNAME_MAP
+        .with(|p| {
+            let usernames = p.borrow();
+            // legacy usernames whose key was already taken are stored as written, and naming
+            // one exactly must reach that account rather than the holder of the key
+            usernames.get(&username.trim().to_string()).or_else(|| usernames.get(&key))
+        })
+        // retired legacy usernames are reserved as written too
+        .or_else(|| active_retired_username(&username.trim().to_string()).map(|retired| retired.principal))
+        .or_else(|| active_retired_username(&key).map(|retired| retired.principal))
+}
+
+/// The reservation of a retired username key, if it has not expired.
+fn active_retired_username(key: &String) -> Option<RetiredUsername> {
+    RETIRED_USERNAME_MAP
+        .with(|p| p.borrow().get(key))
+        .filter(|retired| retired.is_active(api::time()))
+}
+
+/// Reserve a username key for `principal` until `expires_at`, keeping `RETIRED_USERNAME_INDEX`
+/// in step.
+fn retire_username(key: String, principal: Principal, expires_at: u64) {
+    release_retired_username(&key);
+    RETIRED_USERNAME_INDEX.with(|p| p.borrow_mut().insert(RetiredKey { principal, key: key.clone() }, ()));
+    RETIRED_USERNAME_MAP.with(|p| p.borrow_mut().insert(key, RetiredUsername { principal, expires_at }));
+}
+
+/// End the reservation of a retired username key, if any.
+fn release_retired_username(key: &String) {
+    let previous = RETIRED_USERNAME_MAP.with(|p| p.borrow_mut().remove(key));
+    if let Some(previous) = previous {
+        RETIRED_USERNAME_INDEX.with(|p| {
+            p.borrow_mut().remove(&RetiredKey { principal: previous.principal, key: key.clone() })
+        });
+    }
+}
+
+/// Username keys retired by an account, whether or not their reservation has expired.
+fn retired_usernames(principal: Principal) -> Vec<String> {
+    RETIRED_USERNAME_INDEX.with(|p| {
+        p.borrow()
+            .range(RetiredKey { principal, key: String::new() }..)
+            .take_while(|(key, _)| key.principal == principal)
+            .map(|(key, _)| key.key)
+            .collect()
+    })
+}
+
+/// Drop the expired reservations of usernames retired by an account.
+fn prune_retired_usernames(principal: Principal, now: u64) {
+    for key in retired_usernames(principal) {
+        let expired = RETIRED_USERNAME_MAP
+            .with(|p| p.borrow().get(&key))
+            .filter(|retired| retired.is_active(now))
+            .is_none();
+        if expired {
+            release_retired_username(&key);
+        }
+    }
+}
+
+/// Whether a username key is unavailable to `principal`: held by any account, or retired by
+/// another account and still reserved. Expired reservations are dropped along the way.
+fn username_unavailable(key: &String, principal: Principal, now: u64) -> bool {
+    if USERNAME_MAP.with(|p| p.borrow().contains_key(key)) {
+        return true;
+    }
+    match RETIRED_USERNAME_MAP.with(|p| p.borrow().get(key)) {
+        Some(retired) if !retired.is_active(now) => {
+            release_retired_username(key);
+            false
+        }
+        Some(retired) => retired.principal != principal,
+        None => false,
+    }
+}
+
+/// Move `principal`'s entry in `USERNAME_MAP` from `old_username` to `new_key`, unless the new
+/// key is unavailable to it. Returns the key the user's entry was stored under: the folded
+/// key, or the exact spelling for a legacy entry, if the user had an entry at all.
+fn move_username(principal: Principal, old_username: &str, new_key: &String, now: u64) -> ContactsResult<Option<String>> {
+    let old_key = username::username_key(old_username);
+    let holds_old_key = USERNAME_MAP.with(|p| p.borrow().get(&old_key)) == Some(principal);
+    // changing only the case or spelling of a key the user holds keeps the current entry
+    let keeps_key = holds_old_key && *new_key == old_key;
+    if !keeps_key && username_unavailable(new_key, principal, now) {
+        return Err(ContactsError::Conflict("Username already taken".into()));
+    }
+
+    USERNAME_MAP.with(|p| {
+        let mut usernames = p.borrow_mut();
+        let legacy_key = old_username.to_string();
+        let stored_key = if holds_old_key {
+            Some(old_key)
+        } else {
+            (usernames.get(&legacy_key) == Some(principal)).then_some(legacy_key)
+        };
+        if let Some(stored_key) = &stored_key {
+            usernames.remove(stored_key);
+        }
+        usernames.insert(new_key.clone(), principal);
+        Ok(stored_key)
+    })
+}
+
+/// Re-key `USERNAME_MAP`, written before keys were case-folded, by `username_key`. Usernames
+/// then keep their exact spelling as key if their folded key is already taken. Entries were
+/// keyed by the username as written, so a key goes first to the account whose username already
+/// equals it, and the holder of an existing key never changes.
+fn rebuild_username_map() {
+    let users: Vec<(Principal, String)> =
+        USER_MAP.with(|p| p.borrow().iter().map(|(principal, user)| (principal, user.username)).collect());
+    USERNAME_MAP.with(|p| {
+        let mut usernames = p.borrow_mut();
+        let keys: Vec<String> = usernames.iter().map(|(key, _)| key).collect();
+        for key in keys {
+            usernames.remove(&key);
+        }
+
+        let (canonical, legacy): (Vec<_>, Vec<_>) = users
+            .into_iter()
+            .partition(|(_, username)| *username == username::username_key(username));
+        for (principal, username) in canonical.into_iter().chain(legacy) {
+            let key = username::username_key(&username);
+            if !usernames.contains_key(&key) {
+                usernames.insert(key, principal);
+                continue;
+            }
+            ic_cdk::println!(
+                "/post_upgrade [WARN] - Username {:?} collides with another account once case-folded",
+                username
+            );
+            if !usernames.contains_key(&username) {
+                usernames.insert(username, principal);
+            } else {
+                ic_cdk::println!("/post_upgrade [WARN] - Username {:?} is already taken as written", username);
+            }
+        }
+    });
+}
+
+/// Continue removing deleted accounts' data in a later message.
+fn schedule_account_deletions() {
+    ic_cdk_timers::set_timer(Duration::ZERO, || {
+        if continue_account_deletions() {
+            schedule_account_deletions();
+        }
+    });
+}
+
+/// Do one batch of work on the oldest pending account deletion: remove up to
+/// `DELETION_CONTACTS_PER_MESSAGE` of its contacts, withdrawing each from the users it was
+/// shared with. Returns whether work remains.
+fn continue_account_deletions() -> bool {
+    let Some((principal, mut deletion)) = PENDING_DELETIONS.with(|p| p.borrow().first_key_value()) else {
+        return false;
+    };
+
+    let removed = deletion.contacts_removed as usize;
+    let batch = &deletion.contacts[removed..(removed + DELETION_CONTACTS_PER_MESSAGE).min(deletion.contacts.len())];
+    for &contact_id in batch {
+        unshare_contact(contact_id);
+        if let Some(contact) = CONTACT_MAP.with(|p| p.borrow_mut().remove(&contact_id)) {
+            unindex_contact(contact_id, &contact);
+        }
+        CONTACT_OWNER_MAP.with(|p| p.borrow_mut().remove(&contact_id));
+    }
+    deletion.contacts_removed += batch.len() as u64;
+
+    if deletion.contacts_removed as usize >= deletion.contacts.len() {
+        PENDING_DELETIONS.with(|p| p.borrow_mut().remove(&principal));
+        ic_cdk::println!("/delete_account [DONE] - Principal={:?} All data removed", principal.to_string());
+    } else {
+        ic_cdk::println!(
+            "/delete_account [INFO] - Principal={:?} Removed {} of {} contacts",
+            principal.to_string(),
+            deletion.contacts_removed,
+            deletion.contacts.len()
+        );
+        PENDING_DELETIONS.with(|p| p.borrow_mut().insert(principal, deletion));
+    }
+    !PENDING_DELETIONS.with(|p| p.borrow().is_empty())
+}
+
+/// IDs of every contact the user can see: their own and those shared with them.
+fn visible_contact_ids(user: &User) -> BTreeSet<ContactID> {
+    user.contacts
+        .iter()
+        .chain(user.shared_contacts.iter())
+        .copied()
+        .collect()
+}
+
+/// Load the given contacts, skipping any that no longer exist.
+fn load_contacts(contact_ids: &[ContactID]) -> Vec<Contact> {
+    CONTACT_MAP.with(|contact_map| {
+        let contacts = contact_map.borrow();
+        contact_ids
+            .iter()
+            // legacy records may carry no ID or a client-supplied one
+            .filter_map(|id| contacts.get(id).map(|c| c.with_id(*id)))
+            .collect()
+    })
 }
 
 /// whomai i call
 #[query]
 fn whoami() -> (Principal, Option<String>) {
-    let user_id = get_user_id();
+    // the anonymous principal is told who it is, but never has an account
+    let user_id = get_user_id().unwrap_or_else(|_| Principal::anonymous());
     ic_cdk::println!("/whoami [QUERY] - Principal={:?}", user_id.to_text());
     let user: Option<User> = USER_MAP.with(|user_map| user_map.borrow().get(&user_id));
     let username = user.map(|u| u.username);
@@ -64,9 +854,12 @@ fn whoami() -> (Principal, Option<String>) {
 }
 
 /// Create a new user account by providing a unique username.
+///
+/// Usernames are NFKC-normalised and unique regardless of case. They must follow the rules
+/// of `username::parse_username` and must not be reserved.
 #[update]
-fn create_account(new_user: NewUser) -> httpish::BasicResponse {
-    let principal = get_user_id();
+fn create_account(new_user: NewUser) -> ContactsResult<()> {
+    let principal = get_user_id()?;
     ic_cdk::println!(
         "/create_account [UPDATE] - Principal={:?} Username={}",
         principal.to_string(),
@@ -77,190 +870,1089 @@ fn create_account(new_user: NewUser) -> httpish::BasicResponse {
     let user_exists: bool = USER_MAP.with(|p| p.borrow().contains_key(&principal));
     if user_exists {
         ic_cdk::println!("/create_account [REJECT] - User already has an account");
-        return httpish::BasicResponse::Conflict("User already has an account".into());
+        return Err(ContactsError::Conflict("User already has an account".into()));
     }
 
+    let username = match username::parse_username(&new_user.username) {
+        Ok(username) => username,
+        Err(reason) => {
+            ic_cdk::println!("/create_account [REJECT] - Invalid username: {}", reason);
+            return Err(ContactsError::InvalidInput { field: "username".into(), reason });
+        }
+    };
+
     // check if username is already taken
-    let username_taken: bool = USERNAME_MAP.with(|p| p.borrow().contains_key(&new_user.username));
+    let username_taken: bool = username_unavailable(&username.key, principal, api::time());
 
     if username_taken {
         ic_cdk::println!("/create_account [REJECT] - Username already taken");
-        return httpish::BasicResponse::Conflict("Username already taken".into());
+        return Err(ContactsError::Conflict("Username already taken".into()));
     }
 
     // create new user
     ic_cdk::println!("/create_account [INFO] - Creating new user");
     let user = User {
-        username: new_user.username.clone(),
+        username: username.display,
         contacts: Vec::new(),
         shared_contacts: Vec::new(),
+        default_country: None,
     };
 
     USER_MAP.with(|p| p.borrow_mut().insert(principal, user.clone()));
-    USERNAME_MAP.with(|p| p.borrow_mut().insert(new_user.username.clone(), principal));
+    USERNAME_MAP.with(|p| p.borrow_mut().insert(username.key, principal));
 
     ic_cdk::println!("/create_account [DONE] - User: {:?}", user);
-    httpish::BasicResponse::Success("Account created successfully".into())
+    Ok(())
 }
 
-/// Get the list of contacts for the current user.
-#[query]
-fn get_contacts() -> (httpish::BasicResponse, Vec<Contact>) {
-    let user_id = get_user_id();
+/// Change the current user's username, following the same rules as `create_account`.
+///
+/// With `keep_old_reserved`, the old username stays reserved for this user and keeps
+/// resolving to them for `USERNAME_GRACE_PERIOD`, so shares addressed to it still arrive;
+/// otherwise it is free for others to claim immediately. A legacy username stored as written,
+/// because its case-folded form belongs to another account, is reserved as written.
+#[update]
+fn change_username(new_username: String, keep_old_reserved: bool) -> ContactsResult<()> {
+    let user_id = get_user_id()?;
     ic_cdk::println!(
-        "/get_contacts [QUERY] - Principal={:?}",
+        "/change_username [UPDATE] - Principal={:?} Username={} KeepOldReserved={}",
+        user_id.to_string(),
+        new_username,
+        keep_old_reserved
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/change_username [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let mut user = user.unwrap();
+
+    let username = match username::parse_username(&new_username) {
+        Ok(username) => username,
+        Err(reason) => {
+            ic_cdk::println!("/change_username [REJECT] - Invalid username: {}", reason);
+            return Err(ContactsError::InvalidInput { field: "username".into(), reason });
+        }
+    };
+
+    let old_key = match move_username(user_id, &user.username, &username.key, api::time()) {
+        Ok(old_key) => old_key,
+        Err(error) => {
+            ic_cdk::println!("/change_username [REJECT] - Username already taken");
+            return Err(error);
+        }
+    };
+    // reclaiming one of the user's own retired usernames ends its reservation
+    release_retired_username(&username.key);
+    prune_retired_usernames(user_id, api::time());
+    match old_key {
+        Some(old_key) if keep_old_reserved && old_key != username.key => {
+            retire_username(old_key, user_id, api::time() + USERNAME_GRACE_PERIOD);
+        }
+        _ => {}
+    }
+    user.username = username.display;
+    USER_MAP.with(|p| p.borrow_mut().insert(user_id, user.clone()));
+
+    ic_cdk::println!("/change_username [DONE] - User: {:?}", user);
+    Ok(())
+}
+
+/// Delete the current user's account and everything it owns.
+///
+/// The account and its username are gone as soon as this returns. Its contacts are removed
+/// and withdrawn from everyone they were shared with in batches: the first runs in this
+/// message and the rest continue in follow-up messages, so very large address books never
+/// exceed the instruction limit. Contacts others shared with the user are left untouched.
+#[update]
+fn delete_account() -> ContactsResult<()> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/delete_account [UPDATE] - Principal={:?}",
         user_id.to_string()
     );
 
-    let contact_ids: Result<Vec<ContactID>, httpish::BasicResponse> = USER_MAP.with(|user_map| {
-        user_map
-            .borrow()
-            .get(&user_id)
-            .map_or(Err(httpish::BasicResponse::Unauthorized), |u| {
-                Ok(u.contacts.clone())
-            })
+    let user: Option<User> = USER_MAP.with(|p| p.borrow_mut().remove(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/delete_account [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    // free the username, including old ones still reserved for the user
+    USERNAME_MAP.with(|p| {
+        let mut usernames = p.borrow_mut();
+        let key = username::username_key(&user.username);
+        if usernames.get(&key) == Some(user_id) {
+            usernames.remove(&key);
+        } else {
+            usernames.remove(&user.username);
+        }
     });
+    for key in retired_usernames(user_id) {
+        release_retired_username(&key);
+    }
 
-    if contact_ids.is_err() {
-        ic_cdk::println!("/get_contacts [REJECT] - User not found");
-        return (httpish::BasicResponse::Unauthorized, Vec::new());
+    // signing in from a linked principal starts a fresh account again
+    for (principal, _) in linked_principals(user_id) {
+        set_principal_link(principal, None);
+    }
+    for principal in requested_principals(user_id) {
+        set_link_request(principal, user_id, None);
     }
 
-    let contacts = CONTACT_MAP.with(|contact_map| {
-        let contacts = contact_map.borrow();
-        contact_ids
-            .unwrap()
-            .iter()
-            .map(|id| contacts.get(id).unwrap().clone())
-            .collect()
+    // contacts shared with the user are no longer shared with anyone through them
+    SHARE_INDEX.with(|p| {
+        let mut index = p.borrow_mut();
+        for &contact_id in &user.shared_contacts {
+            index.remove(&ShareKey { contact_id, recipient: user_id });
+        }
     });
 
-    ic_cdk::println!("/get_contacts [DONE] - Contacts: {:?}", contacts);
-    (
-        httpish::BasicResponse::Success("Contacts retrieved successfully".into()),
-        contacts,
-    )
+    // an account re-created and deleted again before its earlier deletion finished
+    // picks up the contacts it had left
+    let mut contacts = user.contacts;
+    if let Some(earlier) = PENDING_DELETIONS.with(|p| p.borrow().get(&user_id)) {
+        contacts.extend_from_slice(&earlier.contacts[earlier.contacts_removed as usize..]);
+    }
+    let contact_count = contacts.len();
+    // a timer is already working through the queue when other deletions are pending
+    let in_progress = !PENDING_DELETIONS.with(|p| p.borrow().is_empty());
+    PENDING_DELETIONS.with(|p| p.borrow_mut().insert(user_id, AccountDeletion::new(contacts)));
+
+    if continue_account_deletions() && !in_progress {
+        schedule_account_deletions();
+    }
+
+    ic_cdk::println!("/delete_account [DONE] - Account deleted, removing {} contacts", contact_count);
+    Ok(())
 }
 
-/// Create a new contact for the current user.
-#[update(name = "create_contact")]
-fn create_contact(new_contact: Contact) -> httpish::BasicResponse {
-    let user_id = get_user_id();
+/// Offer to link another principal, such as a second device or Internet Identity anchor, to
+/// the current user's account.
+///
+/// The link takes effect once that principal calls `confirm_link` with the account's username
+/// within `LINK_REQUEST_TTL`. It must not have an account of its own. A principal can have
+/// requests from several accounts at once; a new request from the same account replaces the
+/// previous one. At most `MAX_PENDING_LINK_REQUESTS` requests per account can be pending.
+#[update]
+fn request_link(principal: Principal) -> ContactsResult<()> {
+    let user_id = get_user_id()?;
     ic_cdk::println!(
-        "/create_contact [UPDATE] - Principal={:?} Contact={:?}",
+        "/request_link [UPDATE] - Principal={:?} Device={:?}",
         user_id.to_string(),
-        new_contact
+        principal.to_string()
     );
 
     let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
     if user.is_none() {
-        ic_cdk::println!("/create_contact [REJECT] - User not found");
-        return httpish::BasicResponse::Unauthorized;
+        ic_cdk::println!("/request_link [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
     }
-    let user = user.unwrap();
 
-    let new_contact_id = CONTACT_MAP.with(|p| {
-        let mut contacts = p.borrow_mut();
-        let new_id = contacts.len() as u64;
-        contacts.insert(new_id, new_contact.clone());
-        new_id
-    });
+    if principal == Principal::anonymous() {
+        ic_cdk::println!("/request_link [REJECT] - Anonymous principal");
+        return Err(ContactsError::InvalidInput {
+            field: "principal".into(),
+            reason: "must not be the anonymous principal".into(),
+        });
+    }
+    let link = PRINCIPAL_LINK_MAP.with(|p| p.borrow().get(&principal));
+    if principal == user_id || link.as_ref().is_some_and(|link| link.account == user_id) {
+        ic_cdk::println!("/request_link [REJECT] - Already linked");
+        return Err(ContactsError::Conflict("Principal already linked to this account".into()));
+    }
+    let has_account = USER_MAP.with(|p| p.borrow().contains_key(&principal)) || link.is_some();
+    if has_account {
+        ic_cdk::println!("/request_link [REJECT] - Principal has an account");
+        return Err(ContactsError::Conflict("Principal already has an account".into()));
+    }
+    if linked_principals(user_id).len() >= MAX_LINKED_PRINCIPALS {
+        ic_cdk::println!("/request_link [REJECT] - Too many linked principals");
+        return Err(ContactsError::QuotaExceeded);
+    }
+    let now = api::time();
+    let replaces = LINK_REQUEST_MAP.with(|p| p.borrow().contains_key(&LinkRequestKey { principal, account: user_id }));
+    if prune_link_requests(user_id, now) >= MAX_PENDING_LINK_REQUESTS && !replaces {
+        ic_cdk::println!("/request_link [REJECT] - Too many pending link requests");
+        return Err(ContactsError::QuotaExceeded);
+    }
 
-    let mut updated_user = user.clone();
-    updated_user.contacts.push(new_contact_id);
-    USER_MAP.with(|p| p.borrow_mut().insert(user_id, updated_user));
+    let request = LinkRequest { account: user_id, expires_at: now + LINK_REQUEST_TTL };
+    set_link_request(principal, user_id, Some(request));
 
-    ic_cdk::println!("/create_contact [DONE] - Contact: {:?}", new_contact);
-    httpish::BasicResponse::Success("Contact created successfully".into())
-}
-
-// #[update]
-// fn edit_contact(contact_id: u64, updated_contact: NewContact) -> Result<(), String> {
-//     let user_id = get_user_id();
-//     let mut users = USER_MAP.lock().unwrap();
-
-//     if let Some(user) = users.get_mut(&user_id) {
-//         if let Some(contact) = user.contacts.iter_mut().find(|c| c.id == contact_id) {
-//             contact.name = updated_contact.name;
-//             contact.email = updated_contact.email;
-//             contact.phone = updated_contact.phone;
-//             Ok(())
-//         } else {
-//             Err("Contact not found".to_string())
-//         }
-//     } else {
-//         Err("User not found".to_string())
-//     }
-// }
-
-// #[update]
-// fn delete_contact(contact_id: u64) -> Result<(), String> {
-//     let user_id = get_user_id();
-//     let mut users = USER_MAP.lock().unwrap();
-
-//     if let Some(user) = users.get_mut(&user_id) {
-//         if user.contacts.iter().any(|c| c.id == contact_id) {
-//             user.contacts.retain(|c| c.id != contact_id);
-//             Ok(())
-//         } else {
-//             Err("Contact not found".to_string())
-//         }
-//     } else {
-//         Err("User not found".to_string())
-//     }
-// }
-
-// #[update]
-// fn share_contact(contact_id: u64, recipient_username: String) -> Result<(), String> {
-//     let owner_id = get_user_id();
-//     let mut users = USER_MAP.lock().unwrap();
-
-//     if let Some(owner) = users.get(&owner_id) {
-//         if owner.contacts.iter().any(|c| c.id == contact_id) {
-//             if let Some(recipient) = users
-//                 .values_mut()
-//                 .find(|u| u.username == recipient_username)
-//             {
-//                 recipient.shared_contacts.push(contact_id);
-//                 Ok(())
-//             } else {
-//                 Err("Recipient not found".to_string())
-//             }
-//         } else {
-//             Err("Contact not found".to_string())
-//         }
-//     } else {
-//         Err("Owner not found".to_string())
-//     }
-// }
-
-// #[update]
-// fn revoke_shared_contact(contact_id: u64, recipient_username: String) -> Result<(), String> {
-//     let owner_id = get_user_id();
-//     let mut users = USER_MAP.lock().unwrap();
-
-//     // Check if the owner exists and has the contact in their list.
-//     if let Some(owner) = users.get(&owner_id) {
-//         if owner.contacts.iter().any(|c| c.id == contact_id) {
-//             // Now, find the recipient by username and get a mutable reference to modify their shared_contacts.
-//             if let Some(recipient) = users
-//                 .values_mut()
-//                 .find(|user| user.username == recipient_username)
-//             {
-//                 // Check if the contact is indeed shared with the recipient and remove it if so.
-//                 if recipient.shared_contacts.contains(&contact_id) {
-//                     recipient.shared_contacts.retain(|&id| id != contact_id);
-//                     return Ok(());
-//                 } else {
-//                     return Err("Contact not shared with this user".to_string());
-//                 }
-//             } else {
-//                 return Err("Recipient not found".to_string());
-//             }
-//         } else {
-//             return Err("Contact not found in owner's contact list".to_string());
-//         }
-//     } else {
-//         return Err("Owner not found".to_string());
-//     }
-// }
+    ic_cdk::println!("/request_link [DONE] - Waiting for confirmation");
+    Ok(())
+}
+
+/// Confirm a link requested with `request_link`, after which the caller signs in to the account
+/// with the given username. Naming the account picks which request to confirm and guards
+/// against confirming an unexpected one; the caller's other requests are dropped.
+#[update]
+fn confirm_link(username: String) -> ContactsResult<()> {
+    let caller = get_caller()?;
+    ic_cdk::println!(
+        "/confirm_link [UPDATE] - Principal={:?} Username={}",
+        caller.to_string(),
+        username
+    );
+
+    let has_account = USER_MAP.with(|p| p.borrow().contains_key(&caller))
+        || PRINCIPAL_LINK_MAP.with(|p| p.borrow().contains_key(&caller));
+    if has_account {
+        ic_cdk::println!("/confirm_link [REJECT] - Principal has an account");
+        return Err(ContactsError::Conflict("Principal already has an account".into()));
+    }
+
+    let requests = pending_link_requests(caller);
+    let key = username::username_key(&username);
+    let request = requests.iter().find(|request| {
+        USER_MAP
+            .with(|p| p.borrow().get(&request.account))
+            .is_some_and(|account| username::username_key(&account.username) == key)
+    });
+    let Some(request) = request.cloned() else {
+        ic_cdk::println!("/confirm_link [REJECT] - No matching link request");
+        return Err(ContactsError::NotFound);
+    };
+    // the account may have linked other principals since it made the request
+    if linked_principals(request.account).len() >= MAX_LINKED_PRINCIPALS {
+        ic_cdk::println!("/confirm_link [REJECT] - Too many linked principals");
+        return Err(ContactsError::QuotaExceeded);
+    }
+
+    for other in requests {
+        set_link_request(caller, other.account, None);
+    }
+    set_principal_link(caller, Some(PrincipalLink { account: request.account, linked_at: api::time() }));
+
+    ic_cdk::println!("/confirm_link [DONE] - Linked to {:?}", request.account.to_string());
+    Ok(())
+}
+
+/// Get every principal that signs in to the current user's account, starting with the one
+/// the account was created with.
+#[query]
+fn get_linked_principals() -> ContactsResult<Vec<LinkedPrincipal>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/get_linked_principals [QUERY] - Principal={:?}",
+        user_id.to_string()
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/get_linked_principals [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    let caller = api::caller();
+    let mut principals = vec![LinkedPrincipal { principal: user_id, linked_at: None, is_caller: caller == user_id }];
+    principals.extend(linked_principals(user_id).into_iter().map(|(principal, link)| LinkedPrincipal {
+        principal,
+        linked_at: Some(link.linked_at),
+        is_caller: caller == principal,
+    }));
+
+    ic_cdk::println!("/get_linked_principals [DONE] - Found {} principals", principals.len());
+    Ok(principals)
+}
+
+/// Unlink a principal from the current user's account; it then signs in as a new user. Any
+/// linked principal can be unlinked, including the caller, but not the one the account was
+/// created with.
+#[update]
+fn unlink_principal(principal: Principal) -> ContactsResult<()> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/unlink_principal [UPDATE] - Principal={:?} Device={:?}",
+        user_id.to_string(),
+        principal.to_string()
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/unlink_principal [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    if principal == user_id {
+        ic_cdk::println!("/unlink_principal [REJECT] - Principal created the account");
+        return Err(ContactsError::InvalidInput {
+            field: "principal".into(),
+            reason: "created the account and cannot be unlinked".into(),
+        });
+    }
+    let link = PRINCIPAL_LINK_MAP.with(|p| p.borrow().get(&principal));
+    if link.is_none_or(|link| link.account != user_id) {
+        ic_cdk::println!("/unlink_principal [REJECT] - Principal not linked");
+        return Err(ContactsError::NotFound);
+    }
+
+    set_principal_link(principal, None);
+
+    ic_cdk::println!("/unlink_principal [DONE] - Principal unlinked");
+    Ok(())
+}
+
+/// Get the current user's default country for phone numbers written without a country code.
+#[query]
+fn get_default_country() -> ContactsResult<Option<String>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/get_default_country [QUERY] - Principal={:?}",
+        user_id.to_string()
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/get_default_country [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let default_country = user.unwrap().default_country;
+
+    ic_cdk::println!("/get_default_country [DONE] - Country: {:?}", default_country);
+    Ok(default_country)
+}
+
+/// Set or clear the current user's default country, an ISO 3166-1 alpha-2 code such as "US".
+/// Phone numbers written without a country code are read as national numbers of this country;
+/// without one, they are rejected. Stored contacts are not changed.
+#[update]
+fn set_default_country(country: Option<String>) -> ContactsResult<()> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/set_default_country [UPDATE] - Principal={:?} Country={:?}",
+        user_id.to_string(),
+        country
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/set_default_country [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let mut user = user.unwrap();
+
+    let country = match country.map(|c| validation::normalize_country(&c)).transpose() {
+        Ok(country) => country,
+        Err(reason) => {
+            ic_cdk::println!("/set_default_country [REJECT] - Unsupported country");
+            return Err(ContactsError::InvalidInput { field: "country".into(), reason });
+        }
+    };
+
+    user.default_country = country;
+    USER_MAP.with(|p| p.borrow_mut().insert(user_id, user));
+
+    ic_cdk::println!("/set_default_country [DONE]");
+    Ok(())
+}
+
+/// Get the list of contacts for the current user.
+#[query]
+fn get_contacts() -> ContactsResult<Vec<Contact>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/get_contacts [QUERY] - Principal={:?}",
+        user_id.to_string()
+    );
+
+    let contact_ids: Option<Vec<ContactID>> = USER_MAP.with(|user_map| {
+        user_map.borrow().get(&user_id).map(|u| u.contacts)
+    });
+
+    if contact_ids.is_none() {
+        ic_cdk::println!("/get_contacts [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    let contacts = load_contacts(&contact_ids.unwrap());
+
+    ic_cdk::println!("/get_contacts [DONE] - Contacts: {:?}", contacts);
+    Ok(contacts)
+}
+
+/// Get a page of the current user's contacts, ordered by ascending contact ID.
+/// Pass the `next_cursor` of the previous page to continue, or `None` to start from the beginning.
+#[query]
+fn get_contacts_page(cursor: Option<ContactID>, page_size: u32) -> ContactsResult<ContactPage> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/get_contacts_page [QUERY] - Principal={:?} Cursor={:?} PageSize={}",
+        user_id.to_string(),
+        cursor,
+        page_size
+    );
+
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        ic_cdk::println!("/get_contacts_page [REJECT] - Invalid page size");
+        return Err(ContactsError::InvalidInput {
+            field: "page_size".into(),
+            reason: format!("must be between 1 and {}", MAX_PAGE_SIZE),
+        });
+    }
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/get_contacts_page [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    let mut contact_ids = user.unwrap().contacts;
+    contact_ids.sort_unstable();
+    let remaining: Vec<ContactID> = contact_ids
+        .into_iter()
+        .filter(|&id| cursor.is_none_or(|c| id > c))
+        .collect();
+    let page_ids = &remaining[..remaining.len().min(page_size as usize)];
+    let next_cursor = if remaining.len() > page_ids.len() {
+        page_ids.last().copied()
+    } else {
+        None
+    };
+    let page = ContactPage {
+        contacts: load_contacts(page_ids),
+        next_cursor,
+    };
+
+    ic_cdk::println!("/get_contacts_page [DONE] - Page: {:?}", page);
+    Ok(page)
+}
+
+/// Get the current user's contacts ordered by most recently changed, optionally limited to
+/// those changed at or after `since` (nanoseconds since the UNIX epoch).
+#[query]
+fn get_recently_changed_contacts(since: Option<u64>) -> ContactsResult<Vec<Contact>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/get_recently_changed_contacts [QUERY] - Principal={:?} Since={:?}",
+        user_id.to_string(),
+        since
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/get_recently_changed_contacts [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    let since = since.unwrap_or(0);
+    let mut contacts: Vec<Contact> = load_contacts(&user.unwrap().contacts)
+        .into_iter()
+        .filter(|c| c.updated_at() >= since)
+        .collect();
+    contacts.sort_by(|a, b| b.updated_at().cmp(&a.updated_at()).then(b.id().cmp(&a.id())));
+
+    ic_cdk::println!("/get_recently_changed_contacts [DONE] - Contacts: {:?}", contacts);
+    Ok(contacts)
+}
+
+/// Search the current user's own and shared contacts by name, organization, emails and phones.
+/// Matching is case-insensitive, and every word of the query must appear as a prefix or
+/// substring of a word in the contact. At most `MAX_PAGE_SIZE` contacts are returned.
+#[query]
+fn search_contacts(query: String) -> ContactsResult<Vec<Contact>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/search_contacts [QUERY] - Principal={:?} Query={}",
+        user_id.to_string(),
+        query
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/search_contacts [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    let query_tokens = search::tokenize(&query);
+    if query_tokens.is_empty() {
+        ic_cdk::println!("/search_contacts [REJECT] - Empty query");
+        return Err(ContactsError::InvalidInput {
+            field: "query".into(),
+            reason: "must contain at least one letter or digit".into(),
+        });
+    }
+
+    let match_ids: Vec<ContactID> = visible_contact_ids(&user)
+        .into_iter()
+        .filter(|&id| query_tokens.iter().all(|token| contact_has_term_prefix(id, token)))
+        .take(MAX_PAGE_SIZE as usize)
+        .collect();
+    let contacts = load_contacts(&match_ids);
+
+    ic_cdk::println!("/search_contacts [DONE] - Contacts: {:?}", contacts);
+    Ok(contacts)
+}
+
+/// Fuzzy search the current user's own and shared contacts by name, tolerating typos and
+/// sound-alike spellings. Matches are ranked by score, best first, and at most
+/// `MAX_PAGE_SIZE` are returned.
+#[query]
+fn fuzzy_search_contacts(query: String) -> ContactsResult<Vec<ScoredContact>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/fuzzy_search_contacts [QUERY] - Principal={:?} Query={}",
+        user_id.to_string(),
+        query
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/fuzzy_search_contacts [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    let query_tokens = search::tokenize(&query);
+    if query_tokens.is_empty() {
+        ic_cdk::println!("/fuzzy_search_contacts [REJECT] - Empty query");
+        return Err(ContactsError::InvalidInput {
+            field: "query".into(),
+            reason: "must contain at least one letter or digit".into(),
+        });
+    }
+
+    let visible_ids: Vec<ContactID> = visible_contact_ids(&user).into_iter().collect();
+    let mut matches: Vec<ScoredContact> = load_contacts(&visible_ids)
+        .into_iter()
+        .map(|contact| ScoredContact {
+            score: search::fuzzy_score(&query_tokens, &contact.name),
+            contact,
+        })
+        .filter(|m| m.score >= search::FUZZY_MATCH_THRESHOLD)
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.contact.id().cmp(&b.contact.id())));
+    matches.truncate(MAX_PAGE_SIZE as usize);
+
+    ic_cdk::println!("/fuzzy_search_contacts [DONE] - Matches: {:?}", matches);
+    Ok(matches)
+}
+
+/// Export a single contact, owned by or shared with the current user, as vCard text.
+#[query]
+fn export_vcard(contact_id: ContactID, version: VCardVersion) -> ContactsResult<String> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/export_vcard [QUERY] - Principal={:?} ContactID={} Version={:?}",
+        user_id.to_string(),
+        contact_id,
+        version
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/export_vcard [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    let contact = load_contacts(&[contact_id]).pop();
+    if contact.is_none() {
+        ic_cdk::println!("/export_vcard [REJECT] - Contact not found");
+        return Err(ContactsError::NotFound);
+    }
+
+    if !visible_contact_ids(&user.unwrap()).contains(&contact_id) {
+        ic_cdk::println!("/export_vcard [REJECT] - Contact not visible to user");
+        return Err(ContactsError::Forbidden);
+    }
+
+    let vcard = vcard::to_vcard(&contact.unwrap(), version);
+    ic_cdk::println!("/export_vcard [DONE] - ContactID: {}", contact_id);
+    Ok(vcard)
+}
+
+/// Export the current user's address book as vCard text, one card per contact, optionally
+/// including the contacts shared with them.
+#[query]
+fn export_address_book_vcard(include_shared: bool, version: VCardVersion) -> ContactsResult<String> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/export_address_book_vcard [QUERY] - Principal={:?} IncludeShared={} Version={:?}",
+        user_id.to_string(),
+        include_shared,
+        version
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/export_address_book_vcard [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    let mut contact_ids = user.contacts.clone();
+    if include_shared {
+        contact_ids.extend(user.shared_contacts.iter().copied());
+    }
+    let vcards: String = load_contacts(&contact_ids)
+        .iter()
+        .map(|contact| vcard::to_vcard(contact, version))
+        .collect();
+
+    ic_cdk::println!("/export_address_book_vcard [DONE] - Exported {} contacts", contact_ids.len());
+    Ok(vcards)
+}
+
+/// Export a single contact, owned by or shared with the current user, as jCard JSON.
+#[query]
+fn export_jcard(contact_id: ContactID) -> ContactsResult<String> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/export_jcard [QUERY] - Principal={:?} ContactID={}",
+        user_id.to_string(),
+        contact_id
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/export_jcard [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    let contact = load_contacts(&[contact_id]).pop();
+    if contact.is_none() {
+        ic_cdk::println!("/export_jcard [REJECT] - Contact not found");
+        return Err(ContactsError::NotFound);
+    }
+
+    if !visible_contact_ids(&user.unwrap()).contains(&contact_id) {
+        ic_cdk::println!("/export_jcard [REJECT] - Contact not visible to user");
+        return Err(ContactsError::Forbidden);
+    }
+
+    let jcard = jcard::to_jcard(&contact.unwrap()).to_string();
+    ic_cdk::println!("/export_jcard [DONE] - ContactID: {}", contact_id);
+    Ok(jcard)
+}
+
+/// Export the current user's address book as a JSON array of jCards, optionally including
+/// the contacts shared with them.
+#[query]
+fn export_address_book_jcard(include_shared: bool) -> ContactsResult<String> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/export_address_book_jcard [QUERY] - Principal={:?} IncludeShared={}",
+        user_id.to_string(),
+        include_shared
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/export_address_book_jcard [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    let mut contact_ids = user.contacts.clone();
+    if include_shared {
+        contact_ids.extend(user.shared_contacts.iter().copied());
+    }
+    let jcards: Vec<serde_json::Value> = load_contacts(&contact_ids)
+        .iter()
+        .map(jcard::to_jcard)
+        .collect();
+
+    ic_cdk::println!("/export_address_book_jcard [DONE] - Exported {} contacts", jcards.len());
+    Ok(serde_json::Value::Array(jcards).to_string())
+}
+
+/// Export the current user's own contacts as CSV in the given column layout.
+#[query]
+fn export_csv(layout: CsvLayout) -> ContactsResult<String> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/export_csv [QUERY] - Principal={:?} Layout={:?}",
+        user_id.to_string(),
+        layout
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/export_csv [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+
+    let contacts = load_contacts(&user.unwrap().contacts);
+    let csv = csv::to_csv(&contacts, layout);
+
+    ic_cdk::println!("/export_csv [DONE] - Exported {} contacts", contacts.len());
+    Ok(csv)
+}
+
+/// Get the list of contacts shared with the current user, along with the username of each owner.
+#[query]
+fn get_shared_contacts() -> ContactsResult<Vec<SharedContact>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/get_shared_contacts [QUERY] - Principal={:?}",
+        user_id.to_string()
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/get_shared_contacts [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let shared_ids = user.unwrap().shared_contacts;
+
+    // contacts shared by the same owner need their username looked up only once
+    let mut owners: BTreeMap<Principal, Option<String>> = BTreeMap::new();
+    let shared_contacts: Vec<SharedContact> = CONTACT_MAP.with(|p| {
+        let contacts = p.borrow();
+        shared_ids
+            .iter()
+            .filter_map(|id| {
+                let contact = contacts.get(id)?.with_id(*id);
+                let owner_id = CONTACT_OWNER_MAP.with(|p| p.borrow().get(id))?;
+                let owner = owners
+                    .entry(owner_id)
+                    .or_insert_with(|| USER_MAP.with(|p| p.borrow().get(&owner_id)).map(|u| u.username))
+                    .clone()?;
+                Some(SharedContact { owner, contact })
+            })
+            .collect()
+    });
+
+    ic_cdk::println!("/get_shared_contacts [DONE] - Shared Contacts: {:?}", shared_contacts);
+    Ok(shared_contacts)
+}
+
+/// Create a new contact for the current user, returning the stored contact with its assigned ID.
+#[update(name = "create_contact")]
+fn create_contact(new_contact: NewContact) -> ContactsResult<Contact> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/create_contact [UPDATE] - Principal={:?} Contact={:?}",
+        user_id.to_string(),
+        new_contact
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/create_contact [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let mut user = user.unwrap();
+
+    let new_contact = match validation::validate_contact(new_contact, user.default_country.as_deref()) {
+        Ok(details) => details,
+        Err(error) => {
+            ic_cdk::println!("/create_contact [REJECT] - Invalid contact: {:?}", error);
+            return Err(error);
+        }
+    };
+
+    let new_contact = store_contact(user_id, &mut user, new_contact);
+    USER_MAP.with(|p| p.borrow_mut().insert(user_id, user));
+
+    ic_cdk::println!("/create_contact [DONE] - Contact: {:?}", new_contact);
+    Ok(new_contact)
+}
+
+/// Import contacts for the current user from vCard text, reporting for each card whether it
+/// was imported, skipped as a duplicate, or rejected.
+#[update]
+fn import_vcards(vcards: String) -> ContactsResult<Vec<ImportEntry>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/import_vcards [UPDATE] - Principal={:?} Bytes={}",
+        user_id.to_string(),
+        vcards.len()
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/import_vcards [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let mut user = user.unwrap();
+
+    let cards = vcard::parse_vcards(&vcards);
+    if cards.len() > MAX_IMPORT_ENTRIES {
+        ic_cdk::println!("/import_vcards [REJECT] - Too many cards");
+        return Err(ContactsError::InvalidInput {
+            field: "vcards".into(),
+            reason: format!("must contain at most {} cards", MAX_IMPORT_ENTRIES),
+        });
+    }
+
+    let report = import_entries(user_id, &mut user, cards);
+    USER_MAP.with(|p| p.borrow_mut().insert(user_id, user));
+
+    ic_cdk::println!("/import_vcards [DONE] - Report: {:?}", report);
+    Ok(report)
+}
+
+/// Import contacts for the current user from jCard JSON, either a single jCard or an array
+/// of them, reporting for each jCard whether it was imported, skipped as a duplicate, or
+/// rejected.
+#[update]
+fn import_jcards(jcards: String) -> ContactsResult<Vec<ImportEntry>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/import_jcards [UPDATE] - Principal={:?} Bytes={}",
+        user_id.to_string(),
+        jcards.len()
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/import_jcards [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let mut user = user.unwrap();
+
+    let cards = match jcard::parse_jcards(&jcards) {
+        Ok(cards) => cards,
+        Err(reason) => {
+            ic_cdk::println!("/import_jcards [REJECT] - {}", reason);
+            return Err(ContactsError::InvalidInput { field: "jcards".into(), reason });
+        }
+    };
+    if cards.len() > MAX_IMPORT_ENTRIES {
+        ic_cdk::println!("/import_jcards [REJECT] - Too many jCards");
+        return Err(ContactsError::InvalidInput {
+            field: "jcards".into(),
+            reason: format!("must contain at most {} jCards", MAX_IMPORT_ENTRIES),
+        });
+    }
+
+    let report = import_entries(user_id, &mut user, cards);
+    USER_MAP.with(|p| p.borrow_mut().insert(user_id, user));
+
+    ic_cdk::println!("/import_jcards [DONE] - Report: {:?}", report);
+    Ok(report)
+}
+
+/// Import contacts for the current user from CSV with a header row, such as a Google
+/// Contacts or Outlook export, reporting for each data row whether it was imported,
+/// skipped as a duplicate, or rejected.
+#[update]
+fn import_csv(csv: String) -> ContactsResult<Vec<ImportEntry>> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/import_csv [UPDATE] - Principal={:?} Bytes={}",
+        user_id.to_string(),
+        csv.len()
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/import_csv [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let mut user = user.unwrap();
+
+    let rows = match csv::parse_csv(&csv) {
+        Ok(rows) => rows,
+        Err(reason) => {
+            ic_cdk::println!("/import_csv [REJECT] - {}", reason);
+            return Err(ContactsError::InvalidInput { field: "csv".into(), reason });
+        }
+    };
+    if rows.len() > MAX_IMPORT_ENTRIES {
+        ic_cdk::println!("/import_csv [REJECT] - Too many rows");
+        return Err(ContactsError::InvalidInput {
+            field: "csv".into(),
+            reason: format!("must contain at most {} rows", MAX_IMPORT_ENTRIES),
+        });
+    }
+
+    let report = import_entries(user_id, &mut user, rows);
+    USER_MAP.with(|p| p.borrow_mut().insert(user_id, user));
+
+    ic_cdk::println!("/import_csv [DONE] - Report: {:?}", report);
+    Ok(report)
+}
+
+/// Edit one of the current user's contacts, returning the updated contact.
+#[update]
+fn edit_contact(contact_id: ContactID, updated_contact: NewContact) -> ContactsResult<Contact> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/edit_contact [UPDATE] - Principal={:?} ContactID={} Contact={:?}",
+        user_id.to_string(),
+        contact_id,
+        updated_contact
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/edit_contact [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    let contact: Option<Contact> = CONTACT_MAP.with(|p| p.borrow().get(&contact_id));
+    if contact.is_none() {
+        ic_cdk::println!("/edit_contact [REJECT] - Contact not found");
+        return Err(ContactsError::NotFound);
+    }
+
+    if !user.contacts.contains(&contact_id) {
+        ic_cdk::println!("/edit_contact [REJECT] - Contact not owned by user");
+        return Err(ContactsError::Forbidden);
+    }
+
+    let updated_contact = match validation::validate_contact(updated_contact, user.default_country.as_deref()) {
+        Ok(details) => details,
+        Err(error) => {
+            ic_cdk::println!("/edit_contact [REJECT] - Invalid contact: {:?}", error);
+            return Err(error);
+        }
+    };
+
+    let mut contact = contact.unwrap().with_id(contact_id);
+    unindex_contact(contact_id, &contact);
+    contact.update(updated_contact, api::time());
+    CONTACT_MAP.with(|p| p.borrow_mut().insert(contact_id, contact.clone()));
+    index_contact(contact_id, &contact);
+
+    ic_cdk::println!("/edit_contact [DONE] - Contact: {:?}", contact);
+    Ok(contact)
+}
+
+/// Delete one of the current user's contacts, withdrawing it from everyone it was shared with.
+#[update]
+fn delete_contact(contact_id: ContactID) -> ContactsResult<()> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/delete_contact [UPDATE] - Principal={:?} ContactID={}",
+        user_id.to_string(),
+        contact_id
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/delete_contact [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    let contact_exists: bool = CONTACT_MAP.with(|p| p.borrow().contains_key(&contact_id));
+    if !contact_exists {
+        ic_cdk::println!("/delete_contact [REJECT] - Contact not found");
+        return Err(ContactsError::NotFound);
+    }
+
+    if !user.contacts.contains(&contact_id) {
+        ic_cdk::println!("/delete_contact [REJECT] - Contact not owned by user");
+        return Err(ContactsError::Forbidden);
+    }
+
+    unshare_contact(contact_id);
+
+    let mut updated_user = user.clone();
+    updated_user.contacts.retain(|&id| id != contact_id);
+    USER_MAP.with(|p| p.borrow_mut().insert(user_id, updated_user));
+    if let Some(contact) = CONTACT_MAP.with(|p| p.borrow_mut().remove(&contact_id)) {
+        unindex_contact(contact_id, &contact);
+    }
+    CONTACT_OWNER_MAP.with(|p| p.borrow_mut().remove(&contact_id));
+
+    ic_cdk::println!("/delete_contact [DONE] - ContactID: {}", contact_id);
+    Ok(())
+}
+
+/// Share one of the current user's contacts with another user, identified by username.
+#[update]
+fn share_contact(contact_id: ContactID, recipient_username: String) -> ContactsResult<()> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/share_contact [UPDATE] - Principal={:?} ContactID={} Recipient={}",
+        user_id.to_string(),
+        contact_id,
+        recipient_username
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/share_contact [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    let contact_exists: bool = CONTACT_MAP.with(|p| p.borrow().contains_key(&contact_id));
+    if !contact_exists {
+        ic_cdk::println!("/share_contact [REJECT] - Contact not found");
+        return Err(ContactsError::NotFound);
+    }
+
+    if !user.contacts.contains(&contact_id) {
+        ic_cdk::println!("/share_contact [REJECT] - Contact not owned by user");
+        return Err(ContactsError::Forbidden);
+    }
+
+    let recipient_id: Option<Principal> = principal_for_username(&recipient_username);
+    if recipient_id.is_none() {
+        ic_cdk::println!("/share_contact [REJECT] - Recipient not found");
+        return Err(ContactsError::NotFound);
+    }
+    let recipient_id = recipient_id.unwrap();
+
+    if recipient_id == user_id {
+        ic_cdk::println!("/share_contact [REJECT] - Cannot share with self");
+        return Err(ContactsError::Conflict("Cannot share a contact with yourself".into()));
+    }
+
+    let mut recipient: User = USER_MAP.with(|p| p.borrow().get(&recipient_id)).unwrap();
+    if recipient.shared_contacts.contains(&contact_id) {
+        ic_cdk::println!("/share_contact [REJECT] - Contact already shared with recipient");
+        return Err(ContactsError::Conflict("Contact already shared with this user".into()));
+    }
+
+    recipient.shared_contacts.push(contact_id);
+    USER_MAP.with(|p| p.borrow_mut().insert(recipient_id, recipient));
+    SHARE_INDEX.with(|p| p.borrow_mut().insert(ShareKey { contact_id, recipient: recipient_id }, ()));
+
+    ic_cdk::println!("/share_contact [DONE] - ContactID: {} Recipient: {}", contact_id, recipient_username);
+    Ok(())
+}
+
+/// Revoke a contact previously shared by the current user with another user, identified by username.
+#[update]
+fn revoke_shared_contact(contact_id: ContactID, recipient_username: String) -> ContactsResult<()> {
+    let user_id = get_user_id()?;
+    ic_cdk::println!(
+        "/revoke_shared_contact [UPDATE] - Principal={:?} ContactID={} Recipient={}",
+        user_id.to_string(),
+        contact_id,
+        recipient_username
+    );
+
+    let user: Option<User> = USER_MAP.with(|p| p.borrow().get(&user_id));
+    if user.is_none() {
+        ic_cdk::println!("/revoke_shared_contact [REJECT] - User not found");
+        return Err(ContactsError::NotRegistered);
+    }
+    let user = user.unwrap();
+
+    if !user.contacts.contains(&contact_id) {
+        ic_cdk::println!("/revoke_shared_contact [REJECT] - Contact not owned by user");
+        return Err(ContactsError::Forbidden);
+    }
+
+    let recipient_id: Option<Principal> = principal_for_username(&recipient_username);
+    if recipient_id.is_none() {
+        ic_cdk::println!("/revoke_shared_contact [REJECT] - Recipient not found");
+        return Err(ContactsError::NotFound);
+    }
+    let recipient_id = recipient_id.unwrap();
+
+    let mut recipient: User = USER_MAP.with(|p| p.borrow().get(&recipient_id)).unwrap();
+    if !recipient.shared_contacts.contains(&contact_id) {
+        ic_cdk::println!("/revoke_shared_contact [REJECT] - Contact not shared with recipient");
+        return Err(ContactsError::Conflict("Contact not shared with this user".into()));
+    }
+
+    recipient.shared_contacts.retain(|&id| id != contact_id);
+    USER_MAP.with(|p| p.borrow_mut().insert(recipient_id, recipient));
+    SHARE_INDEX.with(|p| p.borrow_mut().remove(&ShareKey { contact_id, recipient: recipient_id }));
+
+    ic_cdk::println!("/revoke_shared_contact [DONE] - ContactID: {} Recipient: {}", contact_id, recipient_username);
+    Ok(())
+}
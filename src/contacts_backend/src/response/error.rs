@@ -0,0 +1,24 @@
+use candid::{CandidType, Deserialize};
+
+/// Errors returned by the canister's endpoints.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub enum ContactsError {
+    /// The caller is the anonymous principal, which cannot act on any account.
+    AnonymousCaller,
+    /// The caller does not have an account.
+    NotRegistered,
+    /// The caller is not allowed to act on the requested resource.
+    Forbidden,
+    /// The requested resource does not exist.
+    NotFound,
+    /// An argument failed validation.
+    InvalidInput { field: String, reason: String },
+    /// The caller has reached a storage limit.
+    QuotaExceeded,
+    /// The caller is making requests too quickly.
+    RateLimited,
+    /// The request conflicts with the current state.
+    Conflict(String),
+}
+
+pub type ContactsResult<T> = Result<T, ContactsError>;
@@ -1,9 +0,0 @@
-use candid::{CandidType, Deserialize};
-
-#[derive(CandidType, Deserialize, Debug)]
-pub enum BasicResponse {
-    Success(String),
-    Unauthorized,
-    Forbidden,
-    Conflict(String)
-}
\ No newline at end of file
@@ -1 +1 @@
-pub mod httpish;
\ No newline at end of file
+pub mod error;
\ No newline at end of file
@@ -0,0 +1,157 @@
+use std::collections::BTreeSet;
+
+use crate::data::contact::Contact;
+
+/// Only this many leading characters of a token are indexed, bounding the number of
+/// suffixes stored per token.
+const MAX_INDEXED_TOKEN_LEN: usize = 64;
+
+/// Split text into lowercase alphanumeric tokens.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Every term under which a contact is indexed.
+///
+/// Each token of the name, organization, emails and phones is indexed by all of its
+/// suffixes, so that a prefix lookup on the index finds both token prefixes and substrings
+/// within a token. Phones are also indexed as their bare digits, so "5551234" finds
+/// "555-1234".
+pub fn index_terms(contact: &Contact) -> BTreeSet<String> {
+    let mut tokens = tokenize(&contact.name);
+    tokens.extend(tokenize(&contact.organization));
+    for email in &contact.emails {
+        tokens.extend(tokenize(&email.value));
+    }
+    for phone in &contact.phones {
+        tokens.extend(tokenize(&phone.value));
+        tokens.push(phone.value.chars().filter(|c| c.is_ascii_digit()).collect());
+    }
+
+    tokens
+        .iter()
+        .flat_map(|token| {
+            let chars: Vec<char> = token.chars().take(MAX_INDEXED_TOKEN_LEN).collect();
+            (0..chars.len())
+                .map(|start| chars[start..].iter().collect::<String>())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Similarity at or above which a name is considered a fuzzy match.
+pub const FUZZY_MATCH_THRESHOLD: f64 = 0.6;
+
+/// Similarity given to a name word that starts with the query word.
+const PREFIX_SIMILARITY: f64 = 0.9;
+
+/// Fraction of the remaining gap to a perfect score closed when a name word sounds like
+/// the query word, so sound-alikes rank higher without tying with closer spellings.
+const PHONETIC_BOOST: f64 = 0.5;
+
+/// Score how closely a name matches a query, between 0 (no match) and 1 (exact match).
+///
+/// Each query word is compared against its best-matching word in the name using edit
+/// distance, prefix and Soundex matching, and the per-word scores are averaged.
+pub fn fuzzy_score(query_tokens: &[String], name: &str) -> f64 {
+    let name_tokens = tokenize(name);
+    if query_tokens.is_empty() || name_tokens.is_empty() {
+        return 0.0;
+    }
+
+    let total: f64 = query_tokens
+        .iter()
+        .map(|q| {
+            name_tokens
+                .iter()
+                .map(|n| word_similarity(q, n))
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    total / query_tokens.len() as f64
+}
+
+fn word_similarity(query: &str, word: &str) -> f64 {
+    let query: Vec<char> = query.chars().collect();
+    let word: Vec<char> = word.chars().collect();
+    let max_len = query.len().max(word.len());
+    let mut similarity = 1.0 - edit_distance(&query, &word) as f64 / max_len as f64;
+
+    if query.len() >= 2 && word.starts_with(&query) {
+        similarity = similarity.max(PREFIX_SIMILARITY);
+    }
+    if soundex(&query).is_some_and(|code| Some(code) == soundex(&word)) {
+        similarity += (1.0 - similarity) * PHONETIC_BOOST;
+    }
+    similarity
+}
+
+/// Optimal string alignment distance: Levenshtein distance that also counts swapping two
+/// adjacent characters ("Jonh" / "John") as a single edit.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Soundex code of a word, with the first letter replaced by its Soundex digit so that
+/// words starting with letters that sound alike ("Katherine" / "Catherine") share a code.
+/// Returns `None` for words without any ASCII letters.
+fn soundex(word: &[char]) -> Option<String> {
+    fn digit(c: char) -> Option<char> {
+        match c {
+            'b' | 'f' | 'p' | 'v' => Some('1'),
+            'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+            'd' | 't' => Some('3'),
+            'l' => Some('4'),
+            'm' | 'n' => Some('5'),
+            'r' => Some('6'),
+            _ => None,
+        }
+    }
+
+    let letters: Vec<char> = word
+        .iter()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let first = *letters.first()?;
+
+    let mut code = String::new();
+    let mut last = digit(first);
+    for &c in &letters[1..] {
+        let d = digit(c);
+        if let Some(digit) = d.filter(|_| d != last) {
+            code.push(digit);
+        }
+        // 'h' and 'w' do not separate letters with the same code
+        if c != 'h' && c != 'w' {
+            last = d;
+        }
+    }
+    code.truncate(3);
+    while code.len() < 3 {
+        code.push('0');
+    }
+
+    // group the first letter with its sound-alikes rather than keeping it verbatim
+    let group = digit(first).unwrap_or('0');
+    Some(format!("{}{}", group, code))
+}
@@ -1,11 +1,14 @@
 #[cfg(test)]
 mod tests {
-    use crate::{data::{self, contact}, response::httpish};
+    use crate::{csv::CsvLayout, data::{self, contact}, response::error::{ContactsError, ContactsResult}, vcard::VCardVersion};
 
-    use candid::{self, decode_args, encode_one, utils::ArgumentDecoder, CandidType, Principal};
+    use candid::{self, decode_args, encode_args, encode_one, utils::ArgumentDecoder, CandidType, Encode, Principal};
     use ic_cdk::api::management_canister::main::CanisterId;
     use pocket_ic::{PocketIc, WasmResult};
+    use ic_stable_structures::Storable;
     use serde::Deserialize;
+    use std::borrow::Cow;
+    use std::time::Duration;
 
     fn load_contacts_backend_wasm() -> Vec<u8> {
         let wasm_path = "/Users/isaacgordon/Documents/ic/contacts/target/wasm32-unknown-unknown/release/contacts_backend.wasm";
@@ -25,6 +28,20 @@ mod tests {
         (pic, canister_id)
     }
 
+    /// Contact details with a single unlabelled email and phone, skipping empty ones.
+    fn contact_details(name: &str, email: &str, phone: &str) -> data::new_contact::NewContact {
+        let single = |value: &str| match value {
+            "" => Vec::new(),
+            value => vec![data::labelled_value::LabelledValue::new("", value)],
+        };
+        data::new_contact::NewContact {
+            name: name.to_string(),
+            emails: single(email),
+            phones: single(phone),
+            ..Default::default()
+        }
+    }
+
     /// Helper function for calling pocket ic update call
     pub fn update<T>(
         ic: &PocketIc,
@@ -50,14 +67,35 @@ mod tests {
     }
     
 
+    /// Helper function for calling pocket ic query call
+    pub fn query<T>(
+        ic: &PocketIc,
+        sender: Principal,
+        receiver: Principal,
+        method: &str,
+        args: Vec<u8>
+    ) -> Result<T, String>
+    where
+        T: CandidType + for<'a> Deserialize<'a> + for<'a> ArgumentDecoder<'a>,
+    {
+        match ic.query_call(receiver, sender, method, args) {
+            Ok(WasmResult::Reply(data)) => {
+                let tuple: T = decode_args(&data).expect("Failed to decode reply");
+                Ok(tuple)
+            },
+            Ok(WasmResult::Reject(error_message)) => Err(error_message.to_string()),
+            Err(user_error) => Err(user_error.to_string()),
+        }
+    }
+
     /// Helper function to call the create_account function on the canister, and return a Result that can be checked immediately.
     fn call_create_account(
         pic: &PocketIc,
         canister_id: CanisterId,
         principal: Principal,
         new_user: data::new_user::NewUser,
-    ) -> Result<(httpish::BasicResponse,), String> {
-        update::<(httpish::BasicResponse,)>
+    ) -> Result<(ContactsResult<()>,), String> {
+        update::<(ContactsResult<()>,)>
         (
             &pic, 
             principal, 
@@ -67,14 +105,140 @@ mod tests {
         )
     }
 
+    /// Helper function to call change_username on the canister, and return a Result that can be checked immediately.
+    fn call_change_username(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        new_username: &str,
+        keep_old_reserved: bool,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update::<(ContactsResult<()>,)>(
+            &pic,
+            principal,
+            canister_id,
+            "change_username",
+            encode_args((new_username, keep_old_reserved)).unwrap()
+        )
+    }
+
+    /// Helper function to call delete_account on the canister, and return a Result that can be checked immediately.
+    fn call_delete_account(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update::<(ContactsResult<()>,)>(
+            &pic,
+            principal,
+            canister_id,
+            "delete_account",
+            encode_one(()).unwrap()
+        )
+    }
+
+    /// Helper function to call request_link on the canister, and return a Result that can be checked immediately.
+    fn call_request_link(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        device: Principal,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update(
+            &pic,
+            principal,
+            canister_id,
+            "request_link",
+            encode_one(device).unwrap()
+        )
+    }
+
+    /// Helper function to call confirm_link on the canister, and return a Result that can be checked immediately.
+    fn call_confirm_link(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        username: &str,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update(
+            &pic,
+            principal,
+            canister_id,
+            "confirm_link",
+            encode_one(username).unwrap()
+        )
+    }
+
+    /// Helper function to call get_linked_principals on the canister, and return a Result that can be checked immediately.
+    fn call_get_linked_principals(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+    ) -> Result<(ContactsResult<Vec<data::linked_principal::LinkedPrincipal>>,), String> {
+        update(
+            &pic,
+            principal,
+            canister_id,
+            "get_linked_principals",
+            encode_one(()).unwrap()
+        )
+    }
+
+    /// Helper function to call unlink_principal on the canister, and return a Result that can be checked immediately.
+    fn call_unlink_principal(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        device: Principal,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update(
+            &pic,
+            principal,
+            canister_id,
+            "unlink_principal",
+            encode_one(device).unwrap()
+        )
+    }
+
+    /// Helper function to call whoami on the canister, and return a Result that can be checked immediately.
+    fn call_whoami(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+    ) -> Result<(Principal, Option<String>), String> {
+        update(
+            &pic,
+            principal,
+            canister_id,
+            "whoami",
+            encode_one(()).unwrap()
+        )
+    }
+
+    /// Helper function to call set_default_country on the canister, and return a Result that can be checked immediately.
+    fn call_set_default_country(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        country: Option<&str>,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update::<(ContactsResult<()>,)>(
+            &pic,
+            principal,
+            canister_id,
+            "set_default_country",
+            encode_one(country.map(str::to_string)).unwrap()
+        )
+    }
+
     /// Helper function to call create_contact on the canister, and return a Result that can be checked immediately.
     fn call_create_contact(
         pic: &PocketIc,
         canister_id: CanisterId,
         principal: Principal,
-        new_contact: data::contact::Contact,
-    ) ->  Result<(httpish::BasicResponse,), String>{
-        update::<(httpish::BasicResponse,)>(
+        new_contact: data::new_contact::NewContact,
+    ) ->  Result<(ContactsResult<data::contact::Contact>,), String>{
+        update::<(ContactsResult<data::contact::Contact>,)>(
             &pic, 
             principal, 
             canister_id, 
@@ -88,7 +252,7 @@ mod tests {
         pic: &PocketIc,
         canister_id: CanisterId,
         principal: Principal,
-    ) -> Result<(httpish::BasicResponse, Vec<data::contact::Contact>), String> {
+    ) -> Result<(ContactsResult<Vec<data::contact::Contact>>,), String> {
         update(
             &pic, 
             principal, 
@@ -98,6 +262,283 @@ mod tests {
         )   
     }
 
+    /// Helper function to call get_contacts_page on the canister, and return a Result that can be checked immediately.
+    fn call_get_contacts_page(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        cursor: Option<contact::ContactID>,
+        page_size: u32,
+    ) -> Result<(ContactsResult<data::contact_page::ContactPage>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "get_contacts_page", 
+            encode_args((cursor, page_size)).unwrap()
+        )   
+    }
+
+    /// Helper function to call get_recently_changed_contacts on the canister, and return a Result that can be checked immediately.
+    fn call_get_recently_changed_contacts(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        since: Option<u64>,
+    ) -> Result<(ContactsResult<Vec<data::contact::Contact>>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "get_recently_changed_contacts", 
+            encode_one(since).unwrap()
+        )   
+    }
+
+    /// Helper function to call search_contacts on the canister, and return a Result that can be checked immediately.
+    fn call_search_contacts(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        query: &str,
+    ) -> Result<(ContactsResult<Vec<data::contact::Contact>>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "search_contacts", 
+            encode_one(query).unwrap()
+        )   
+    }
+
+    /// Helper function to call fuzzy_search_contacts on the canister, and return a Result that can be checked immediately.
+    fn call_fuzzy_search_contacts(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        query: &str,
+    ) -> Result<(ContactsResult<Vec<data::scored_contact::ScoredContact>>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "fuzzy_search_contacts", 
+            encode_one(query).unwrap()
+        )   
+    }
+
+    /// Helper function to call export_vcard on the canister, and return a Result that can be checked immediately.
+    fn call_export_vcard(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        contact_id: contact::ContactID,
+        version: VCardVersion,
+    ) -> Result<(ContactsResult<String>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "export_vcard", 
+            encode_args((contact_id, version)).unwrap()
+        )   
+    }
+
+    /// Helper function to call export_address_book_vcard on the canister, and return a Result that can be checked immediately.
+    fn call_export_address_book_vcard(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        include_shared: bool,
+        version: VCardVersion,
+    ) -> Result<(ContactsResult<String>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "export_address_book_vcard", 
+            encode_args((include_shared, version)).unwrap()
+        )   
+    }
+
+    /// Helper function to call get_shared_contacts on the canister, and return a Result that can be checked immediately.
+    fn call_get_shared_contacts(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+    ) -> Result<(ContactsResult<Vec<data::shared_contact::SharedContact>>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "get_shared_contacts", 
+            encode_one(()).unwrap()
+        )   
+    }
+
+    /// Helper function to call import_vcards on the canister, and return a Result that can be checked immediately.
+    fn call_import_vcards(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        vcards: &str,
+    ) -> Result<(ContactsResult<Vec<data::import_entry::ImportEntry>>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "import_vcards", 
+            encode_one(vcards).unwrap()
+        )   
+    }
+
+    /// Helper function to call import_csv on the canister, and return a Result that can be checked immediately.
+    fn call_import_csv(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        csv: &str,
+    ) -> Result<(ContactsResult<Vec<data::import_entry::ImportEntry>>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "import_csv", 
+            encode_one(csv).unwrap()
+        )   
+    }
+
+    /// Helper function to call export_csv on the canister, and return a Result that can be checked immediately.
+    fn call_export_csv(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        layout: CsvLayout,
+    ) -> Result<(ContactsResult<String>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "export_csv", 
+            encode_one(layout).unwrap()
+        )   
+    }
+
+    /// Helper function to call export_jcard on the canister, and return a Result that can be checked immediately.
+    fn call_export_jcard(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        contact_id: contact::ContactID,
+    ) -> Result<(ContactsResult<String>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "export_jcard", 
+            encode_one(contact_id).unwrap()
+        )   
+    }
+
+    /// Helper function to call export_address_book_jcard on the canister, and return a Result that can be checked immediately.
+    fn call_export_address_book_jcard(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        include_shared: bool,
+    ) -> Result<(ContactsResult<String>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "export_address_book_jcard", 
+            encode_one(include_shared).unwrap()
+        )   
+    }
+
+    /// Helper function to call import_jcards on the canister, and return a Result that can be checked immediately.
+    fn call_import_jcards(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        jcards: &str,
+    ) -> Result<(ContactsResult<Vec<data::import_entry::ImportEntry>>,), String> {
+        update(
+            &pic, 
+            principal, 
+            canister_id, 
+            "import_jcards", 
+            encode_one(jcards).unwrap()
+        )   
+    }
+
+    /// Helper function to call edit_contact on the canister, and return a Result that can be checked immediately.
+    fn call_edit_contact(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        contact_id: contact::ContactID,
+        updated_contact: data::new_contact::NewContact,
+    ) -> Result<(ContactsResult<data::contact::Contact>,), String> {
+        update::<(ContactsResult<data::contact::Contact>,)>(
+            &pic, 
+            principal, 
+            canister_id, 
+            "edit_contact", 
+            encode_args((contact_id, updated_contact)).unwrap()
+        )
+    }
+
+    /// Helper function to call delete_contact on the canister, and return a Result that can be checked immediately.
+    fn call_delete_contact(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        contact_id: contact::ContactID,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update::<(ContactsResult<()>,)>(
+            &pic, 
+            principal, 
+            canister_id, 
+            "delete_contact", 
+            encode_one(contact_id).unwrap()
+        )
+    }
+
+    /// Helper function to call share_contact on the canister, and return a Result that can be checked immediately.
+    fn call_share_contact(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        contact_id: contact::ContactID,
+        recipient_username: &str,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update::<(ContactsResult<()>,)>(
+            &pic, 
+            principal, 
+            canister_id, 
+            "share_contact", 
+            encode_args((contact_id, recipient_username)).unwrap()
+        )
+    }
+
+    /// Helper function to call revoke_shared_contact on the canister, and return a Result that can be checked immediately.
+    fn call_revoke_shared_contact(
+        pic: &PocketIc,
+        canister_id: CanisterId,
+        principal: Principal,
+        contact_id: contact::ContactID,
+        recipient_username: &str,
+    ) -> Result<(ContactsResult<()>,), String> {
+        update::<(ContactsResult<()>,)>(
+            &pic, 
+            principal, 
+            canister_id, 
+            "revoke_shared_contact", 
+            encode_args((contact_id, recipient_username)).unwrap()
+        )
+    }
+
     /// Testing the create_account function and its adherence to the requirements.
     /// The requirements are:
     /// 1. A user can create an account with a unique username.
@@ -132,9 +573,9 @@ mod tests {
         let first_account_create = call_create_account(&pic, canister_id, principal1, user1);
         assert!(
             first_account_create.is_ok_and(|response| 
-                matches!(response.0, httpish::BasicResponse::Success(_))
+                matches!(response.0, Ok(_))
             ),
-            "First account creation failed when it should not have. Expected a `Success` response, but didn't get one."
+            "First account creation failed when it should not have. Expected `Ok`, but didn't get one."
         );
 
         // Test another user creates a new account with a different username. (Requirement 1)
@@ -142,7 +583,7 @@ mod tests {
         let second_account_create = call_create_account(&pic, canister_id, principal2, user2);
         assert!(
             second_account_create.is_ok_and(|response| 
-                matches!(response.0, httpish::BasicResponse::Success(_))
+                matches!(response.0, Ok(_))
             ),
             "Second account creation failed when it should not have. Expected `Ok` but got `Err`."
         );
@@ -153,7 +594,7 @@ mod tests {
             call_create_account(&pic, canister_id, principal3, user1_duplicate);
         assert!(
             already_registered_username.is_ok_and(|response| 
-                matches!(response.0, httpish::BasicResponse::Conflict(_))
+                matches!(response.0, Err(ContactsError::Conflict(_)))
             ),
             "Username should already be taken. Expected `Err` but got `Ok`."
         );
@@ -163,7 +604,7 @@ mod tests {
         let already_registered_user = call_create_account(&pic, canister_id, principal2, user3);
         assert!(
             already_registered_user.is_ok_and(|response| 
-                matches!(response.0, httpish::BasicResponse::Conflict(_))
+                matches!(response.0, Err(ContactsError::Conflict(_)))
             ),
             "User should already have an account. Expected `Err` but got `Ok`."
         );
@@ -175,17 +616,11 @@ mod tests {
     /// 2. A user can retrieve their contacts.
     /// 3. A user cannot retrieve contacts if they do not have an account.
     /// 4. A user cannot create a contact if they do not have an account.
+    /// 5. The canister assigns the contact ID.
     #[test]
     fn test_create_and_retrieve_contacts() {
         // Set up a user and a contact.
-        let mut contact_id_counter = data::counter::Counter::new();
-        
-        let new_contact = data::contact::Contact::new( 
-            "John Doe".to_string(),
-            "johndoe@example.com".to_string(),
-            "123-456-7890".to_string(),
-            None
-        );
+        let new_contact = contact_details("John Doe", "johndoe@example.com", "+11234567890");
 
         // init pocket-ic canister
         let (pic, canister_id) = deploy_test_canister();
@@ -197,10 +632,8 @@ mod tests {
         println!("Creating contact for principal1 without an account...");
         let create_contact_no_account = call_create_contact(&pic, canister_id, principal, new_contact.clone());
         assert!(
-            create_contact_no_account.is_ok_and(|response| 
-                matches!(response.0, httpish::BasicResponse::Unauthorized)
-            ),
-            "User should not have been able to create a contact without an account. Expected `Unauthorized`."
+            create_contact_no_account.is_err(),
+            "User should not have been able to create a contact without an account. Expected it to be dropped by inspect_message."
         );
 
         // Test creating an account.
@@ -214,29 +647,1808 @@ mod tests {
         println!("Creating contact for principal1...");
         let create_contact = call_create_contact(&pic, canister_id, principal, new_contact.clone());
         assert!(
-            create_contact.is_ok_and(|response| 
-                matches!(response.0, httpish::BasicResponse::Success(_))
+            create_contact.as_ref().is_ok_and(|response| 
+                matches!(response.0, Ok(_))
             ),
             "Contact creation failed when it should not have. Expected `Ok` but got `Err`."
         );
 
+        // Test that the created contact was assigned an ID. (Requirement 5)
+        let created_contact = create_contact.unwrap().0.expect("Created contact was not returned");
+        assert!(
+            created_contact.id().is_some(),
+            "Created contact was not assigned an ID."
+        );
+
         // Test retrieving contacts. (Requirement 2)
         println!("Retrieving contacts for principal1...");
         let contacts = call_get_contacts(&pic, canister_id, principal);
         assert!(
             contacts.as_ref().is_ok_and(|response| 
-                matches!(response.0, httpish::BasicResponse::Success(_))
+                matches!(response.0, Ok(_))
             ),
             "Failed to retrieve contacts. Expected `Ok` but got `Err`."
         );
 
         // Test that contacts contain the created contact
-        let retrieved_contacts = contacts.unwrap().1;
+        let retrieved_contacts = contacts.unwrap().0.unwrap();
         println!("Number of retrieved contacts: {}", retrieved_contacts.len());
         assert!(
-            retrieved_contacts.contains(&new_contact),
+            retrieved_contacts.contains(&created_contact),
             "Retrieved contacts do not contain the created contact."
         );
         
     }
+
+    /// Testing the ability to edit contacts.
+    /// The requirements are:
+    /// 1. A user can edit a contact they own.
+    /// 2. A user cannot edit a contact owned by someone else.
+    /// 3. Editing a contact that does not exist returns `NotFound`.
+    #[test]
+    fn test_edit_contact() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        // Set up an owner and another user, each with an account.
+        let owner = Principal::from_slice(&[0x05]);
+        let other = Principal::from_slice(&[0x06]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "other".to_string() });
+
+        let new_contact = contact_details("John Doe", "johndoe@example.com", "+11234567890");
+        let created_contact = call_create_contact(&pic, canister_id, owner, new_contact).unwrap().0.unwrap();
+        let contact_id = created_contact.id().unwrap();
+
+        let updated_contact = contact_details("Jane Doe", "janedoe@example.com", "+10987654321");
+
+        // Test editing a contact owned by someone else. (Requirement 2)
+        println!("Editing owner's contact as another user...");
+        let edit_not_owner = call_edit_contact(&pic, canister_id, other, contact_id, updated_contact.clone());
+        assert!(
+            edit_not_owner.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Forbidden))
+            ),
+            "User should not be able to edit a contact they do not own. Expected `Forbidden`."
+        );
+
+        // Test editing a contact that does not exist. (Requirement 3)
+        println!("Editing a contact that does not exist...");
+        let edit_missing = call_edit_contact(&pic, canister_id, owner, 42, updated_contact.clone());
+        assert!(
+            edit_missing.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::NotFound))
+            ),
+            "Editing a missing contact should fail. Expected `NotFound`."
+        );
+
+        // Test editing an owned contact. (Requirement 1)
+        println!("Editing owner's contact...");
+        let edit = call_edit_contact(&pic, canister_id, owner, contact_id, updated_contact.clone());
+        assert!(
+            edit.is_ok_and(|response| 
+                matches!(response.0, Ok(_))
+            ),
+            "Contact edit failed when it should not have. Expected `Ok`."
+        );
+
+        let retrieved_contacts = call_get_contacts(&pic, canister_id, owner).unwrap().0.unwrap();
+        assert!(
+            retrieved_contacts.iter().any(|c| c.name == updated_contact.name && c.emails == updated_contact.emails && c.phones == updated_contact.phones),
+            "Retrieved contacts do not reflect the edit."
+        );
+    }
+
+    /// Testing the ability to delete contacts.
+    /// The requirements are:
+    /// 1. A user cannot delete a contact owned by someone else.
+    /// 2. A user can delete a contact they own, and it no longer appears in their contacts.
+    /// 3. Deleting a contact that does not exist returns `NotFound`.
+    #[test]
+    fn test_delete_contact() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        // Set up an owner and another user, each with an account.
+        let owner = Principal::from_slice(&[0x07]);
+        let other = Principal::from_slice(&[0x08]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "other".to_string() });
+
+        let new_contact = contact_details("John Doe", "johndoe@example.com", "+11234567890");
+        let created_contact = call_create_contact(&pic, canister_id, owner, new_contact).unwrap().0.unwrap();
+        let contact_id = created_contact.id().unwrap();
+
+        // Test deleting a contact owned by someone else. (Requirement 1)
+        println!("Deleting owner's contact as another user...");
+        let delete_not_owner = call_delete_contact(&pic, canister_id, other, contact_id);
+        assert!(
+            delete_not_owner.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Forbidden))
+            ),
+            "User should not be able to delete a contact they do not own. Expected `Forbidden`."
+        );
+
+        // Test deleting an owned contact. (Requirement 2)
+        println!("Deleting owner's contact...");
+        let delete = call_delete_contact(&pic, canister_id, owner, contact_id);
+        assert!(
+            delete.is_ok_and(|response| 
+                matches!(response.0, Ok(_))
+            ),
+            "Contact deletion failed when it should not have. Expected `Ok`."
+        );
+
+        let retrieved_contacts = call_get_contacts(&pic, canister_id, owner).unwrap().0.unwrap();
+        assert!(
+            !retrieved_contacts.contains(&created_contact),
+            "Retrieved contacts still contain the deleted contact."
+        );
+
+        // Test deleting the same contact again. (Requirement 3)
+        println!("Deleting a contact that no longer exists...");
+        let delete_again = call_delete_contact(&pic, canister_id, owner, contact_id);
+        assert!(
+            delete_again.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::NotFound))
+            ),
+            "Deleting a missing contact should fail. Expected `NotFound`."
+        );
+    }
+
+    /// Testing the ability to share contacts.
+    /// The requirements are:
+    /// 1. A user can share a contact they own with another user by username.
+    /// 2. A user cannot share a contact owned by someone else.
+    /// 3. Sharing with an unknown username returns `NotFound`.
+    /// 4. A user cannot share a contact with themselves.
+    /// 5. A user cannot share the same contact with the same user twice.
+    #[test]
+    fn test_share_contact() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        // Set up an owner and a recipient, each with an account.
+        let owner = Principal::from_slice(&[0x09]);
+        let recipient = Principal::from_slice(&[0x0a]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_create_account(&pic, canister_id, recipient, data::new_user::NewUser { username: "recipient".to_string() });
+
+        let new_contact = contact_details("John Doe", "johndoe@example.com", "+11234567890");
+        let created_contact = call_create_contact(&pic, canister_id, owner, new_contact).unwrap().0.unwrap();
+        let contact_id = created_contact.id().unwrap();
+
+        // Test sharing a contact owned by someone else. (Requirement 2)
+        println!("Sharing owner's contact as the recipient...");
+        let share_not_owner = call_share_contact(&pic, canister_id, recipient, contact_id, "owner");
+        assert!(
+            share_not_owner.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Forbidden))
+            ),
+            "User should not be able to share a contact they do not own. Expected `Forbidden`."
+        );
+
+        // Test sharing with an unknown username. (Requirement 3)
+        println!("Sharing contact with an unknown username...");
+        let share_unknown = call_share_contact(&pic, canister_id, owner, contact_id, "nobody");
+        assert!(
+            share_unknown.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::NotFound))
+            ),
+            "Sharing with an unknown user should fail. Expected `NotFound`."
+        );
+
+        // Test sharing with yourself. (Requirement 4)
+        println!("Sharing contact with self...");
+        let share_self = call_share_contact(&pic, canister_id, owner, contact_id, "owner");
+        assert!(
+            share_self.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Conflict(_)))
+            ),
+            "Sharing with yourself should fail. Expected `Conflict`."
+        );
+
+        // Test sharing with the recipient. (Requirement 1)
+        println!("Sharing contact with the recipient...");
+        let share = call_share_contact(&pic, canister_id, owner, contact_id, "recipient");
+        assert!(
+            share.is_ok_and(|response| 
+                matches!(response.0, Ok(_))
+            ),
+            "Contact sharing failed when it should not have. Expected `Ok`."
+        );
+
+        // Test sharing with the recipient a second time. (Requirement 5)
+        println!("Sharing contact with the recipient again...");
+        let share_again = call_share_contact(&pic, canister_id, owner, contact_id, "recipient");
+        assert!(
+            share_again.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Conflict(_)))
+            ),
+            "Sharing the same contact twice should fail. Expected `Conflict`."
+        );
+    }
+
+    /// Testing the ability to revoke shared contacts.
+    /// The requirements are:
+    /// 1. A user cannot revoke a share of a contact they do not own.
+    /// 2. Revoking from an unknown username returns `NotFound`.
+    /// 3. Revoking a share that does not exist returns `Conflict`.
+    /// 4. A user can revoke a contact they shared.
+    #[test]
+    fn test_revoke_shared_contact() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        // Set up an owner and a recipient, each with an account.
+        let owner = Principal::from_slice(&[0x0b]);
+        let recipient = Principal::from_slice(&[0x0c]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_create_account(&pic, canister_id, recipient, data::new_user::NewUser { username: "recipient".to_string() });
+
+        let new_contact = contact_details("John Doe", "johndoe@example.com", "+11234567890");
+        let created_contact = call_create_contact(&pic, canister_id, owner, new_contact).unwrap().0.unwrap();
+        let contact_id = created_contact.id().unwrap();
+
+        // Test revoking a share that was never made. (Requirement 3)
+        println!("Revoking a contact that was never shared...");
+        let revoke_unshared = call_revoke_shared_contact(&pic, canister_id, owner, contact_id, "recipient");
+        assert!(
+            revoke_unshared.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Conflict(_)))
+            ),
+            "Revoking a contact that was never shared should fail. Expected `Conflict`."
+        );
+
+        let _ = call_share_contact(&pic, canister_id, owner, contact_id, "recipient");
+
+        // Test revoking a contact as someone other than the owner. (Requirement 1)
+        println!("Revoking owner's contact as the recipient...");
+        let revoke_not_owner = call_revoke_shared_contact(&pic, canister_id, recipient, contact_id, "recipient");
+        assert!(
+            revoke_not_owner.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Forbidden))
+            ),
+            "User should not be able to revoke a contact they do not own. Expected `Forbidden`."
+        );
+
+        // Test revoking from an unknown username. (Requirement 2)
+        println!("Revoking contact from an unknown username...");
+        let revoke_unknown = call_revoke_shared_contact(&pic, canister_id, owner, contact_id, "nobody");
+        assert!(
+            revoke_unknown.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::NotFound))
+            ),
+            "Revoking from an unknown user should fail. Expected `NotFound`."
+        );
+
+        // Test revoking the share. (Requirement 4)
+        println!("Revoking contact from the recipient...");
+        let revoke = call_revoke_shared_contact(&pic, canister_id, owner, contact_id, "recipient");
+        assert!(
+            revoke.is_ok_and(|response| 
+                matches!(response.0, Ok(_))
+            ),
+            "Revoking a shared contact failed when it should not have. Expected `Ok`."
+        );
+    }
+
+    /// Testing the ability to retrieve contacts shared with the current user.
+    /// The requirements are:
+    /// 1. A recipient can see a contact shared with them, along with the owner's username.
+    /// 2. A revoked contact no longer appears in the recipient's shared contacts.
+    /// 3. A deleted contact no longer appears in the recipient's shared contacts.
+    #[test]
+    fn test_get_shared_contacts() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        // Set up an owner and a recipient, each with an account.
+        let owner = Principal::from_slice(&[0x0d]);
+        let recipient = Principal::from_slice(&[0x0e]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_create_account(&pic, canister_id, recipient, data::new_user::NewUser { username: "recipient".to_string() });
+
+        let new_contact = contact_details("John Doe", "johndoe@example.com", "+11234567890");
+        let created_contact = call_create_contact(&pic, canister_id, owner, new_contact).unwrap().0.unwrap();
+        let contact_id = created_contact.id().unwrap();
+
+        // Test retrieving a shared contact. (Requirement 1)
+        println!("Retrieving shared contacts for the recipient...");
+        let _ = call_share_contact(&pic, canister_id, owner, contact_id, "recipient");
+        let shared_contacts = call_get_shared_contacts(&pic, canister_id, recipient).unwrap().0.unwrap();
+        assert!(
+            shared_contacts.iter().any(|s| s.owner == "owner" && s.contact == created_contact),
+            "Shared contacts do not contain the contact shared by the owner."
+        );
+
+        // Test that a revoked contact is no longer shared. (Requirement 2)
+        println!("Retrieving shared contacts after revoking...");
+        let _ = call_revoke_shared_contact(&pic, canister_id, owner, contact_id, "recipient");
+        let shared_contacts = call_get_shared_contacts(&pic, canister_id, recipient).unwrap().0.unwrap();
+        assert!(
+            shared_contacts.is_empty(),
+            "Shared contacts still contain a revoked contact."
+        );
+
+        // Test that a deleted contact is no longer shared. (Requirement 3)
+        println!("Retrieving shared contacts after deleting...");
+        let _ = call_share_contact(&pic, canister_id, owner, contact_id, "recipient");
+        let _ = call_delete_contact(&pic, canister_id, owner, contact_id);
+        let shared_contacts = call_get_shared_contacts(&pic, canister_id, recipient).unwrap().0.unwrap();
+        assert!(
+            shared_contacts.is_empty(),
+            "Shared contacts still contain a deleted contact."
+        );
+    }
+
+    /// Testing that contact IDs are never handed out twice.
+    /// The requirements are:
+    /// 1. Deleting a contact and creating a new one does not reuse the deleted ID.
+    /// 2. Creating a contact after a delete does not overwrite an existing contact.
+    #[test]
+    fn test_contact_ids_not_reused() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x0f]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+
+        let first = contact_details("First", "first@example.com", "+15550000001");
+        let second = contact_details("Second", "second@example.com", "+15550000002");
+        let third = contact_details("Third", "third@example.com", "+15550000003");
+        let first = call_create_contact(&pic, canister_id, owner, first).unwrap().0.unwrap();
+        let second = call_create_contact(&pic, canister_id, owner, second).unwrap().0.unwrap();
+
+        // Delete the second contact, then create another one. (Requirement 1)
+        println!("Deleting the second contact and creating a third...");
+        let _ = call_delete_contact(&pic, canister_id, owner, second.id().unwrap());
+        let third = call_create_contact(&pic, canister_id, owner, third).unwrap().0.unwrap();
+        assert!(
+            third.id() != second.id(),
+            "The deleted contact ID was reused."
+        );
+
+        // Both remaining contacts are still present. (Requirement 2)
+        let retrieved_contacts = call_get_contacts(&pic, canister_id, owner).unwrap().0.unwrap();
+        assert!(
+            retrieved_contacts.len() == 2 && retrieved_contacts.contains(&first) && retrieved_contacts.contains(&third),
+            "Creating a contact after a delete overwrote an existing contact."
+        );
+    }
+
+    /// Testing that stored data survives a canister upgrade.
+    /// The requirements are:
+    /// 1. Accounts and contacts are still readable after an upgrade.
+    /// 2. Contact IDs allocated after an upgrade do not collide with existing ones.
+    #[test]
+    fn test_data_survives_upgrade() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x10]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let new_contact = contact_details("John Doe", "johndoe@example.com", "+11234567890");
+        let created_contact = call_create_contact(&pic, canister_id, owner, new_contact.clone()).unwrap().0.unwrap();
+
+        println!("Upgrading the canister...");
+        pic.upgrade_canister(canister_id, load_contacts_backend_wasm(), encode_one(()).unwrap(), None)
+            .expect("Failed to upgrade the canister");
+
+        // Test that the account and contact survived. (Requirement 1)
+        let retrieved_contacts = call_get_contacts(&pic, canister_id, owner).unwrap().0.unwrap();
+        assert!(
+            retrieved_contacts == vec![created_contact.clone()],
+            "Contacts were not preserved across the upgrade."
+        );
+
+        // Test that new IDs do not collide. (Requirement 2)
+        let next_contact = call_create_contact(&pic, canister_id, owner, new_contact).unwrap().0.unwrap();
+        assert!(
+            next_contact.id() != created_contact.id(),
+            "A contact ID was reused after the upgrade."
+        );
+    }
+
+    /// Contact layout of the first release, stored bare or as `StoredContact::V1`.
+    #[derive(CandidType)]
+    struct LegacyContactV1 {
+        id: Option<contact::ContactID>,
+        name: String,
+        email: String,
+        phone: String,
+    }
+
+    /// Contact layout stored as `StoredContact::V2`, before multi-valued fields.
+    #[derive(CandidType)]
+    struct LegacyContactV2 {
+        id: Option<contact::ContactID>,
+        name: String,
+        email: String,
+        phone: String,
+        created_at: u64,
+        updated_at: u64,
+    }
+
+    /// The variants of `StoredContact` written by older releases.
+    #[derive(CandidType)]
+    enum LegacyStoredContact {
+        V1(LegacyContactV1),
+        V2(LegacyContactV2),
+    }
+
+    /// User layout of the first release, stored bare or as `StoredUser::V1`.
+    #[derive(CandidType)]
+    struct LegacyUserV1 {
+        username: String,
+        contacts: Vec<contact::ContactID>,
+        shared_contacts: Vec<contact::ContactID>,
+    }
+
+    /// The variants of `StoredUser` written by older releases.
+    #[derive(CandidType)]
+    enum LegacyStoredUser {
+        V1(LegacyUserV1),
+    }
+
+    fn legacy_contact_v1() -> LegacyContactV1 {
+        LegacyContactV1 {
+            id: Some(7),
+            name: "John Doe".to_string(),
+            email: "john@example.com".to_string(),
+            phone: String::new(),
+        }
+    }
+
+    fn legacy_user_v1() -> LegacyUserV1 {
+        LegacyUserV1 { username: "alice".to_string(), contacts: vec![1, 2], shared_contacts: vec![3] }
+    }
+
+    /// Testing that contacts stored by older releases are migrated when read.
+    /// The requirements are:
+    /// 1. Bare records written before the versioned envelope existed are read as the current layout.
+    /// 2. Records in each older envelope variant are read as the current layout.
+    /// 3. Migrated single values become the primary, unlabelled entry, and empty ones are dropped.
+    /// 4. Records in the current layout round-trip unchanged.
+    #[test]
+    fn test_legacy_contact_records_migrate() {
+        let read = |bytes: Vec<u8>| <contact::Contact as Storable>::from_bytes(Cow::Owned(bytes));
+
+        // Test a bare record of the first release. (Requirements 1 and 3)
+        let bare = read(Encode!(&legacy_contact_v1()).unwrap());
+        assert_eq!(bare.id(), Some(7), "The ID was not migrated.");
+        assert_eq!(bare.name, "John Doe", "The name was not migrated.");
+        assert_eq!(
+            (bare.structured_name.given.as_str(), bare.structured_name.family.as_str()),
+            ("John", "Doe"),
+            "The structured name should be derived from the name."
+        );
+        assert_eq!(bare.emails, vec![data::labelled_value::LabelledValue::new("", "john@example.com")], "The email was not migrated.");
+        assert!(bare.phones.is_empty(), "An empty phone number should be dropped.");
+        assert_eq!((bare.created_at(), bare.updated_at()), (0, 0), "A record without timestamps should have none.");
+
+        // Test the older envelope variants. (Requirement 2)
+        let v1 = read(Encode!(&LegacyStoredContact::V1(legacy_contact_v1())).unwrap());
+        assert_eq!(v1, bare, "A V1 record should migrate like a bare one.");
+        let v2 = read(
+            Encode!(&LegacyStoredContact::V2(LegacyContactV2 {
+                id: Some(8),
+                name: "Jane Roe".to_string(),
+                email: String::new(),
+                phone: "+15550000000".to_string(),
+                created_at: 10,
+                updated_at: 20,
+            }))
+            .unwrap(),
+        );
+        assert_eq!(v2.id(), Some(8), "The ID was not migrated.");
+        assert!(v2.emails.is_empty(), "An empty email should be dropped.");
+        assert_eq!(v2.phones, vec![data::labelled_value::LabelledValue::new("", "+15550000000")], "The phone number was not migrated.");
+        assert_eq!((v2.created_at(), v2.updated_at()), (10, 20), "The timestamps were not migrated.");
+
+        // Test the current layout. (Requirement 4)
+        assert_eq!(read(v2.to_bytes().into_owned()), v2, "A current record should round-trip unchanged.");
+    }
+
+    /// Testing that users stored by older releases are migrated when read.
+    /// The requirements are:
+    /// 1. Bare records written before the versioned envelope existed are read as the current layout.
+    /// 2. Records in each older envelope variant are read as the current layout.
+    /// 3. Records in the current layout round-trip unchanged.
+    #[test]
+    fn test_legacy_user_records_migrate() {
+        let read = |bytes: Vec<u8>| <data::user::User as Storable>::from_bytes(Cow::Owned(bytes));
+        let check = |user: &data::user::User| {
+            user.username == "alice" && user.contacts == vec![1, 2] && user.shared_contacts == vec![3] && user.default_country.is_none()
+        };
+
+        // Test a bare record of the first release. (Requirement 1)
+        assert!(check(&read(Encode!(&legacy_user_v1()).unwrap())), "A bare user was not migrated.");
+
+        // Test the older envelope variants. (Requirement 2)
+        assert!(check(&read(Encode!(&LegacyStoredUser::V1(legacy_user_v1())).unwrap())), "A V1 user was not migrated.");
+
+        // Test the current layout. (Requirement 3)
+        let user = data::user::User {
+            username: "bob".to_string(),
+            contacts: vec![4],
+            shared_contacts: Vec::new(),
+            default_country: Some("US".to_string()),
+        };
+        let round_trip = read(user.to_bytes().into_owned());
+        assert_eq!(
+            (round_trip.username, round_trip.contacts, round_trip.shared_contacts, round_trip.default_country),
+            (user.username, user.contacts, user.shared_contacts, user.default_country),
+            "A current user should round-trip unchanged."
+        );
+    }
+
+    /// Testing contact timestamps and the recently changed contacts query.
+    /// The requirements are:
+    /// 1. A new contact has equal creation and last-modified times.
+    /// 2. Editing a contact bumps its last-modified time but not its creation time.
+    /// 3. Contacts are returned ordered by most recently changed.
+    /// 4. Contacts changed before `since` are left out.
+    #[test]
+    fn test_get_recently_changed_contacts() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x11]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+
+        let first = contact_details("First", "first@example.com", "+15550000001");
+        let second = contact_details("Second", "second@example.com", "+15550000002");
+        let first = call_create_contact(&pic, canister_id, owner, first).unwrap().0.unwrap();
+        pic.advance_time(Duration::from_secs(60));
+        let second = call_create_contact(&pic, canister_id, owner, second).unwrap().0.unwrap();
+
+        // Test the timestamps of a new contact. (Requirement 1)
+        assert!(
+            first.created_at() > 0 && first.created_at() == first.updated_at(),
+            "A new contact should have equal, non-zero creation and last-modified times."
+        );
+
+        // Test that editing bumps the last-modified time. (Requirement 2)
+        println!("Editing the first contact...");
+        pic.advance_time(Duration::from_secs(60));
+        let edited = call_edit_contact(&pic, canister_id, owner, first.id().unwrap(), contact_details("First Edited", "first@example.com", "+15550000001")).unwrap().0.unwrap();
+        assert!(
+            edited.created_at() == first.created_at() && edited.updated_at() > second.updated_at(),
+            "Editing a contact should bump only its last-modified time."
+        );
+
+        // Test the ordering. (Requirement 3)
+        println!("Retrieving recently changed contacts...");
+        let recent = call_get_recently_changed_contacts(&pic, canister_id, owner, None).unwrap().0.unwrap();
+        assert!(
+            recent == vec![edited.clone(), second.clone()],
+            "Contacts were not ordered by most recently changed."
+        );
+
+        // Test the `since` filter. (Requirement 4)
+        println!("Retrieving contacts changed since the edit...");
+        let recent = call_get_recently_changed_contacts(&pic, canister_id, owner, Some(edited.updated_at())).unwrap().0.unwrap();
+        assert!(
+            recent == vec![edited],
+            "Contacts changed before `since` should be left out."
+        );
+    }
+
+    /// Testing cursor-based pagination of contacts.
+    /// The requirements are:
+    /// 1. Pages are returned in ascending contact ID order with no gaps or repeats.
+    /// 2. The last page has no next cursor.
+    /// 3. A page size of zero or above the maximum is rejected.
+    #[test]
+    fn test_get_contacts_page() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x12]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+
+        let mut created_ids = Vec::new();
+        for i in 0..5 {
+            let new_contact = contact_details(&format!("Contact {}", i), &format!("contact{}@example.com", i), &format!("+1555000{:04}", i));
+            let created_contact = call_create_contact(&pic, canister_id, owner, new_contact).unwrap().0.unwrap();
+            created_ids.push(created_contact.id().unwrap());
+        }
+
+        // Walk every page. (Requirements 1 and 2)
+        println!("Paging through contacts two at a time...");
+        let mut paged_ids = Vec::new();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let page = call_get_contacts_page(&pic, canister_id, owner, cursor, 2).unwrap().0.unwrap();
+            paged_ids.extend(page.contacts.iter().map(|c| c.id().unwrap()));
+            pages += 1;
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert!(
+            paged_ids == created_ids && pages == 3,
+            "Pagination did not return every contact exactly once in ID order."
+        );
+
+        // Test invalid page sizes. (Requirement 3)
+        println!("Requesting pages with invalid sizes...");
+        for page_size in [0, 101] {
+            let page = call_get_contacts_page(&pic, canister_id, owner, None, page_size);
+            assert!(
+                page.is_ok_and(|response| 
+                    matches!(response.0, Err(ContactsError::InvalidInput { .. }))
+                ),
+                "Invalid page size should be rejected. Expected `InvalidInput`."
+            );
+        }
+    }
+
+    /// Testing search across the current user's contacts.
+    /// The requirements are:
+    /// 1. Search is case-insensitive and matches token prefixes and substrings of name, email and phone.
+    /// 2. Every word of the query must match.
+    /// 3. Contacts shared with the user are searchable; other users' contacts are not.
+    /// 4. Edited and deleted contacts are reflected in the results.
+    #[test]
+    fn test_search_contacts() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x13]);
+        let other = Principal::from_slice(&[0x14]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "other".to_string() });
+
+        let john = call_create_contact(&pic, canister_id, owner, contact_details("John Doe", "johndoe@example.com", "+15551234567")).unwrap().0.unwrap();
+        let jane = call_create_contact(&pic, canister_id, owner, contact_details("Jane Roe", "jane@work.org", "+15557654321")).unwrap().0.unwrap();
+        let shared = call_create_contact(&pic, canister_id, other, contact_details("Johanna Smith", "jo@smith.net", "+15550000000")).unwrap().0.unwrap();
+
+        let search = |principal: Principal, query: &str| -> Vec<data::contact::Contact> {
+            call_search_contacts(&pic, canister_id, principal, query).unwrap().0.unwrap()
+        };
+
+        // Test prefix, substring and case-insensitive matching. (Requirement 1)
+        println!("Searching by prefix, substring and phone digits...");
+        assert!(search(owner, "JO") == vec![john.clone()], "Case-insensitive prefix search failed.");
+        assert!(search(owner, "oe") == vec![john.clone(), jane.clone()], "Substring search failed.");
+        assert!(search(owner, "work.org") == vec![jane.clone()], "Email search failed.");
+        assert!(search(owner, "5551234567") == vec![john.clone()], "Phone digits search failed.");
+
+        // Test that every word must match. (Requirement 2)
+        println!("Searching with multiple words...");
+        assert!(search(owner, "jane doe").is_empty(), "Every query word should have to match.");
+
+        // Test scoping to own and shared contacts. (Requirement 3)
+        println!("Searching before and after a contact is shared...");
+        assert!(search(owner, "johanna").is_empty(), "Another user's contact should not be searchable.");
+        let _ = call_share_contact(&pic, canister_id, other, shared.id().unwrap(), "owner");
+        assert!(search(owner, "johanna") == vec![shared], "A shared contact should be searchable.");
+
+        // Test that edits and deletes are reflected. (Requirement 4)
+        println!("Searching after editing and deleting...");
+        let _ = call_edit_contact(&pic, canister_id, owner, jane.id().unwrap(), contact_details("Janet Roe", "janet@home.org", "+15557654321"));
+        assert!(search(owner, "work").is_empty(), "Edited contact still matches its old details.");
+        assert!(search(owner, "janet").len() == 1, "Edited contact does not match its new details.");
+        let _ = call_delete_contact(&pic, canister_id, owner, john.id().unwrap());
+        assert!(search(owner, "john").is_empty(), "Deleted contact is still searchable.");
+
+        // Test an empty query.
+        let empty = call_search_contacts(&pic, canister_id, owner, " - ");
+        assert!(
+            empty.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::InvalidInput { .. }))
+            ),
+            "A query without any words should be rejected. Expected `InvalidInput`."
+        );
+    }
+
+    /// Testing typo-tolerant search by name.
+    /// The requirements are:
+    /// 1. Transposed letters and sound-alike spellings still match.
+    /// 2. Matches are ranked with the closest match first.
+    /// 3. Unrelated names do not match.
+    #[test]
+    fn test_fuzzy_search_contacts() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x15]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+
+        let mut create = |name: &str| {
+            call_create_contact(&pic, canister_id, owner, contact_details(name, "", "")).unwrap().0.unwrap()
+        };
+        let john = create("John Smith");
+        let joan = create("Joan Smith");
+        let catherine = create("Catherine Jones");
+        let _ = create("Zbigniew Brzezinski");
+
+        let fuzzy_search = |query: &str| -> Vec<data::scored_contact::ScoredContact> {
+            call_fuzzy_search_contacts(&pic, canister_id, owner, query).unwrap().0.unwrap()
+        };
+
+        // Test typos and sound-alike spellings. (Requirement 1)
+        println!("Searching with typos...");
+        let results = fuzzy_search("Jonh");
+        assert!(
+            results.first().is_some_and(|m| m.contact == john),
+            "A transposed spelling should match the intended name first."
+        );
+        let results = fuzzy_search("Katherine");
+        assert!(
+            results.iter().any(|m| m.contact == catherine),
+            "A sound-alike spelling should match."
+        );
+
+        // Test ranking. (Requirement 2)
+        println!("Checking ranking...");
+        let results = fuzzy_search("john smith");
+        assert!(
+            results.len() >= 2 && results[0].contact == john && results[1].contact == joan && results[0].score > results[1].score,
+            "The exact match should rank above the near match."
+        );
+
+        // Test unrelated names. (Requirement 3)
+        println!("Searching for an unrelated name...");
+        assert!(
+            fuzzy_search("Xavier").is_empty(),
+            "An unrelated name should not match."
+        );
+    }
+
+    /// Testing vCard export.
+    /// The requirements are:
+    /// 1. A single contact is exported as a vCard with escaped values and CRLF line endings.
+    /// 2. Long lines are folded to at most 75 octets.
+    /// 3. A contact that is neither owned by nor shared with the user cannot be exported.
+    /// 4. The whole address book is exported, with shared contacts only when requested.
+    #[test]
+    fn test_export_vcard() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x16]);
+        let other = Principal::from_slice(&[0x17]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "other".to_string() });
+
+        let john = call_create_contact(&pic, canister_id, owner, contact_details("John Doe, Jr.", "johndoe@example.com", "+15551234567")).unwrap().0.unwrap();
+        let long = call_create_contact(&pic, canister_id, owner, contact_details("Long", &format!("{}@{}.example.com", "a".repeat(60), "b".repeat(40)), "")).unwrap().0.unwrap();
+        let shared = call_create_contact(&pic, canister_id, other, contact_details("Shared Person", "", "")).unwrap().0.unwrap();
+
+        // Test exporting a single contact. (Requirement 1)
+        println!("Exporting a single contact...");
+        let vcard = call_export_vcard(&pic, canister_id, owner, john.id().unwrap(), VCardVersion::V4).unwrap().0.unwrap();
+        assert!(
+            vcard.starts_with("BEGIN:VCARD\r\nVERSION:4.0\r\n") && vcard.ends_with("END:VCARD\r\n"),
+            "Exported vCard is not framed correctly."
+        );
+        assert!(
+            vcard.contains("FN:John Doe\\, Jr.\r\n") && vcard.contains("EMAIL:johndoe@example.com\r\n"),
+            "Exported vCard does not contain the escaped contact details."
+        );
+        let vcard = call_export_vcard(&pic, canister_id, owner, john.id().unwrap(), VCardVersion::V3).unwrap().0.unwrap();
+        assert!(vcard.contains("VERSION:3.0\r\n"), "vCard 3.0 export has the wrong version.");
+
+        // Test line folding. (Requirement 2)
+        println!("Exporting a contact with a long line...");
+        let vcard = call_export_vcard(&pic, canister_id, owner, long.id().unwrap(), VCardVersion::V4).unwrap().0.unwrap();
+        assert!(
+            vcard.split("\r\n").all(|line| line.len() <= 75) && vcard.contains("\r\n "),
+            "Long lines were not folded."
+        );
+
+        // Test exporting someone else's contact. (Requirement 3)
+        println!("Exporting another user's contact...");
+        let forbidden = call_export_vcard(&pic, canister_id, owner, shared.id().unwrap(), VCardVersion::V4);
+        assert!(
+            forbidden.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::Forbidden))
+            ),
+            "User should not be able to export a contact that is not theirs. Expected `Forbidden`."
+        );
+
+        // Test exporting the whole address book. (Requirement 4)
+        println!("Exporting the address book...");
+        let _ = call_share_contact(&pic, canister_id, other, shared.id().unwrap(), "owner");
+        let book = call_export_address_book_vcard(&pic, canister_id, owner, false, VCardVersion::V4).unwrap().0.unwrap();
+        assert!(
+            book.matches("BEGIN:VCARD").count() == 2 && !book.contains("Shared Person"),
+            "Address book export should contain only the user's own contacts."
+        );
+        let book = call_export_address_book_vcard(&pic, canister_id, owner, true, VCardVersion::V4).unwrap().0.unwrap();
+        assert!(
+            book.matches("BEGIN:VCARD").count() == 3 && book.contains("FN:Shared Person"),
+            "Address book export should include shared contacts when requested."
+        );
+    }
+
+    /// Testing vCard import.
+    /// The requirements are:
+    /// 1. Valid cards are imported as contacts owned by the user.
+    /// 2. Cards matching an existing contact or an earlier card are reported as duplicates.
+    /// 3. Malformed cards are rejected without affecting the rest of the import.
+    /// 4. Exported vCards can be imported again.
+    #[test]
+    fn test_import_vcards() {
+        use data::import_entry::ImportOutcome;
+
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x18]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_set_default_country(&pic, canister_id, owner, Some("US"));
+        let existing = call_create_contact(&pic, canister_id, owner, contact_details("Existing Person", "existing@example.com", "555-000-1111")).unwrap().0.unwrap();
+
+        let vcards = [
+            "BEGIN:VCARD\r\nVERSION:4.0\r\nFN:John Doe\\, Jr.\r\nEMAIL:john@exa\r\n mple.com\r\nTEL;VALUE=uri:tel:+1-555-123-4567\r\nEND:VCARD\r\n",
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Person;Existing;;;\r\nEMAIL;TYPE=INTERNET:EXISTING@example.com\r\nTEL:(555) 000-1111\r\nEND:VCARD\r\n",
+            "BEGIN:VCARD\r\nVERSION:4.0\r\nEMAIL:nameless@example.com\r\nEND:VCARD\r\n",
+            "BEGIN:VCARD\r\nVERSION:4.0\r\nFN:John Doe\\, Jr.\r\nEMAIL:john@example.com\r\nTEL:+1-555-123-4567\r\nEND:VCARD\r\n",
+        ].concat();
+
+        println!("Importing vCards...");
+        let report = call_import_vcards(&pic, canister_id, owner, &vcards).unwrap().0.unwrap();
+        assert!(report.len() == 4, "Every card should be reported.");
+
+        // Test the imported card. (Requirement 1)
+        let imported = match &report[0].outcome {
+            ImportOutcome::Imported(contact) => contact.clone(),
+            outcome => panic!("First card should have been imported, got {:?}", outcome),
+        };
+        assert!(
+            imported.name == "John Doe, Jr." && imported.primary_email() == "john@example.com" && imported.primary_phone() == "+15551234567",
+            "Imported contact does not match the card."
+        );
+
+        // Test duplicates of an existing contact and of an earlier card. (Requirement 2)
+        assert!(
+            report[1].outcome == ImportOutcome::Duplicate(existing.id().unwrap()),
+            "A card matching an existing contact should be reported as a duplicate."
+        );
+        assert!(
+            report[3].outcome == ImportOutcome::Duplicate(imported.id().unwrap()),
+            "A card matching an earlier card should be reported as a duplicate."
+        );
+
+        // Test the rejected card. (Requirement 3)
+        assert!(
+            matches!(report[2].outcome, ImportOutcome::Rejected(_)),
+            "A card without a name should be rejected."
+        );
+        let contacts = call_get_contacts(&pic, canister_id, owner).unwrap().0.unwrap();
+        assert!(contacts.len() == 2, "Only the valid, new card should have been stored.");
+
+        // Test round-tripping through export. (Requirement 4)
+        println!("Re-importing an exported address book into another account...");
+        let other = Principal::from_slice(&[0x19]);
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "other".to_string() });
+        let book = call_export_address_book_vcard(&pic, canister_id, owner, false, VCardVersion::V4).unwrap().0.unwrap();
+        let report = call_import_vcards(&pic, canister_id, other, &book).unwrap().0.unwrap();
+        assert!(
+            report.iter().all(|entry| matches!(entry.outcome, ImportOutcome::Imported(_))) && report.len() == 2,
+            "Exported vCards should import cleanly."
+        );
+    }
+
+    /// Testing CSV import and export.
+    /// The requirements are:
+    /// 1. Google Contacts and Outlook headers are recognised and mapped onto contact fields.
+    /// 2. Rows without a name are rejected individually.
+    /// 3. A header without any name column is rejected.
+    /// 4. Exported CSV can be imported again in either layout.
+    #[test]
+    fn test_import_and_export_csv() {
+        use data::import_entry::ImportOutcome;
+
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x1a]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let _ = call_set_default_country(&pic, canister_id, owner, Some("US"));
+
+        // Test Google and Outlook layouts. (Requirements 1 and 2)
+        println!("Importing a Google Contacts CSV...");
+        let google = "Name,Given Name,Family Name,E-mail 1 - Type,E-mail 1 - Value,Phone 1 - Type,Phone 1 - Value\r\n\
+                      \"Doe, John\",John,Doe,* Work,john@example.com ::: john@home.com,Mobile,555-123-4567\r\n\
+                      ,,,,nameless@example.com,,\r\n";
+        let report = call_import_csv(&pic, canister_id, owner, google).unwrap().0.unwrap();
+        assert!(
+            matches!(&report[0].outcome, ImportOutcome::Imported(c) if c.name == "Doe, John" && c.primary_email() == "john@example.com" && c.primary_phone() == "+15551234567"),
+            "Google Contacts row was not mapped onto the contact fields."
+        );
+        assert!(
+            matches!(report[1].outcome, ImportOutcome::Rejected(_)),
+            "A row without a name should be rejected."
+        );
+
+        println!("Importing an Outlook CSV...");
+        let outlook = "First Name,Middle Name,Last Name,E-mail Address,Home Phone,Mobile Phone\n\
+                       Jane,Q,Roe,jane@example.com,,555-765-4321\n";
+        let report = call_import_csv(&pic, canister_id, owner, outlook).unwrap().0.unwrap();
+        assert!(
+            matches!(&report[0].outcome, ImportOutcome::Imported(c) if c.name == "Jane Q Roe" && c.primary_email() == "jane@example.com" && c.primary_phone() == "+15557654321"),
+            "Outlook row was not mapped onto the contact fields."
+        );
+
+        // Test an unrecognised header. (Requirement 3)
+        println!("Importing a CSV without a name column...");
+        let unrecognised = call_import_csv(&pic, canister_id, owner, "Email,Phone\na@b.com,1\n");
+        assert!(
+            unrecognised.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::InvalidInput { .. }))
+            ),
+            "A header without a name column should be rejected. Expected `InvalidInput`."
+        );
+
+        // Test round-tripping through export. (Requirement 4)
+        for layout in [CsvLayout::Google, CsvLayout::Outlook] {
+            println!("Round-tripping through a {:?} export...", layout);
+            let other = Principal::from_slice(&[0x1b, layout as u8]);
+            let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: format!("other{:?}", layout) });
+            let csv = call_export_csv(&pic, canister_id, owner, layout).unwrap().0.unwrap();
+            let report = call_import_csv(&pic, canister_id, other, &csv).unwrap().0.unwrap();
+            assert!(
+                report.len() == 2 && report.iter().all(|entry| matches!(entry.outcome, ImportOutcome::Imported(_))),
+                "Exported CSV should import cleanly."
+            );
+        }
+    }
+
+    /// Testing CSV import of the current Google Contacts export layout.
+    /// The requirements are:
+    /// 1. Name parts, organization and job title are read from the current column names.
+    /// 2. Email, phone and address labels are read from the "Label" columns.
+    #[test]
+    fn test_parse_current_google_csv() {
+        let google = "First Name,Middle Name,Last Name,Name Prefix,Name Suffix,Nickname,Organization Name,Organization Title,\
+                      Organization Department,Labels,E-mail 1 - Label,E-mail 1 - Value,Phone 1 - Label,Phone 1 - Value,\
+                      Phone 2 - Label,Phone 2 - Value,Address 1 - Label,Address 1 - Formatted,Address 1 - Street,\
+                      Address 1 - City,Address 1 - Region,Address 1 - Postal Code,Address 1 - Country\r\n\
+                      John,Q,Doe,Dr.,,JD,Acme Corp,Engineer,R&D,* myContacts,* Work,john@acme.com,Mobile,+15551234567,\
+                      Home,+15557654321,Home,\"1 Main St, Springfield\",1 Main St,Springfield,IL,62701,US\r\n";
+        let contact = crate::csv::parse_csv(google).unwrap().remove(0).unwrap();
+
+        // Test the name, organization and job title. (Requirement 1)
+        assert_eq!(contact.name, "Dr. John Q Doe", "The name was not built from its parts.");
+        assert_eq!(contact.organization, "Acme Corp", "The organization was not read.");
+        assert_eq!(contact.title, "Engineer", "The job title was not read.");
+
+        // Test the labels. (Requirement 2)
+        assert_eq!(
+            contact.emails,
+            vec![data::labelled_value::LabelledValue::new("Work", "john@acme.com")],
+            "The email label was not read."
+        );
+        assert_eq!(
+            contact.phones,
+            vec![
+                data::labelled_value::LabelledValue::new("Mobile", "+15551234567"),
+                data::labelled_value::LabelledValue::new("Home", "+15557654321"),
+            ],
+            "The phone labels were not read."
+        );
+        assert_eq!(
+            contact.addresses.iter().map(|a| (a.label.as_str(), a.street.as_str(), a.locality.as_str())).collect::<Vec<_>>(),
+            vec![("Home", "1 Main St", "Springfield")],
+            "The address label was not read."
+        );
+    }
+
+    /// Testing jCard export and import.
+    /// The requirements are:
+    /// 1. A contact is exported as a jCard with its name, email and phone.
+    /// 2. A single jCard or an array of jCards can be imported, with malformed jCards rejected individually.
+    /// 3. Invalid JSON is rejected.
+    /// 4. An exported address book can be imported again.
+    #[test]
+    fn test_export_and_import_jcard() {
+        use data::import_entry::ImportOutcome;
+
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x1c]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+        let john = call_create_contact(&pic, canister_id, owner, contact_details("John \"JD\" Doe", "johndoe@example.com", "+15551234567")).unwrap().0.unwrap();
+
+        // Test exporting a single contact. (Requirement 1)
+        println!("Exporting a jCard...");
+        let jcard: serde_json::Value = serde_json::from_str(
+            &call_export_jcard(&pic, canister_id, owner, john.id().unwrap()).unwrap().0.unwrap()
+        ).expect("Exported jCard is not valid JSON");
+        assert!(
+            jcard[0] == "vcard"
+                && jcard[1].as_array().unwrap().contains(&serde_json::json!(["fn", {}, "text", "John \"JD\" Doe"]))
+                && jcard[1].as_array().unwrap().contains(&serde_json::json!(["email", {}, "text", "johndoe@example.com"]))
+                && jcard[1].as_array().unwrap().contains(&serde_json::json!(["tel", {}, "text", "+15551234567"])),
+            "Exported jCard does not contain the contact details."
+        );
+
+        // Test importing a single jCard and an array of jCards. (Requirement 2)
+        println!("Importing jCards...");
+        let single = r#"["vcard", [["version", {}, "text", "4.0"], ["n", {}, "text", ["Roe", "Jane", "", "Dr.", ""]], ["tel", {"type": "cell"}, "uri", "tel:+1-555-765-4321"]]]"#;
+        let report = call_import_jcards(&pic, canister_id, owner, single).unwrap().0.unwrap();
+        assert!(
+            matches!(&report[..], [entry] if matches!(&entry.outcome, ImportOutcome::Imported(c) if c.name == "Dr. Jane Roe" && c.primary_phone() == "+15557654321")),
+            "A single jCard was not imported."
+        );
+        let array = r#"[["vcard", [["fn", {}, "text", "Ann Lee"]]], ["vcard", [["email", {}, "text", "nameless@example.com"]]], ["not a jcard"]]"#;
+        let report = call_import_jcards(&pic, canister_id, owner, array).unwrap().0.unwrap();
+        assert!(
+            report.len() == 3
+                && matches!(report[0].outcome, ImportOutcome::Imported(_))
+                && matches!(report[1].outcome, ImportOutcome::Rejected(_))
+                && matches!(report[2].outcome, ImportOutcome::Rejected(_)),
+            "Malformed jCards should be rejected individually."
+        );
+
+        // Test invalid JSON. (Requirement 3)
+        let invalid = call_import_jcards(&pic, canister_id, owner, "[\"vcard\", ");
+        assert!(
+            invalid.is_ok_and(|response| 
+                matches!(response.0, Err(ContactsError::InvalidInput { .. }))
+            ),
+            "Invalid JSON should be rejected. Expected `InvalidInput`."
+        );
+
+        // Test round-tripping through export. (Requirement 4)
+        println!("Re-importing an exported address book into another account...");
+        let other = Principal::from_slice(&[0x1d]);
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "other".to_string() });
+        let book = call_export_address_book_jcard(&pic, canister_id, owner, false).unwrap().0.unwrap();
+        let report = call_import_jcards(&pic, canister_id, other, &book).unwrap().0.unwrap();
+        assert!(
+            report.len() == 3 && report.iter().all(|entry| matches!(entry.outcome, ImportOutcome::Imported(_))),
+            "An exported address book should import cleanly."
+        );
+    }
+
+    /// Testing contacts with structured names and several labelled emails, phones and addresses.
+    /// The requirements are:
+    /// 1. The display name is derived from the structured name when omitted, and vice versa.
+    /// 2. Every labelled value is stored in order, the first being the primary one.
+    /// 3. Contacts can be found by any of their emails or phones and by organization.
+    /// 4. Labels, addresses, organization and title survive a vCard, jCard and CSV round trip.
+    #[test]
+    fn test_multi_valued_contact_fields() {
+        use data::import_entry::ImportOutcome;
+        use data::labelled_value::LabelledValue;
+        use data::postal_address::PostalAddress;
+        use data::structured_name::StructuredName;
+
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x1e]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+
+        // Test name derivation. (Requirement 1)
+        println!("Creating a contact from a structured name...");
+        let details = data::new_contact::NewContact {
+            structured_name: StructuredName {
+                prefix: "Dr.".to_string(),
+                given: "Jane".to_string(),
+                family: "Roe".to_string(),
+                ..Default::default()
+            },
+            emails: vec![LabelledValue::new("work", "jane@work.org"), LabelledValue::new("home", "jane@home.net")],
+            phones: vec![LabelledValue::new("mobile", "+15551234567"), LabelledValue::new("work", "+15557654321")],
+            addresses: vec![PostalAddress {
+                label: "work".to_string(),
+                street: "1 Main St".to_string(),
+                locality: "Springfield".to_string(),
+                region: "IL".to_string(),
+                postal_code: "62701".to_string(),
+                country: "USA".to_string(),
+            }],
+            organization: "Acme Corp".to_string(),
+            title: "Engineer".to_string(),
+            ..Default::default()
+        };
+        let jane = call_create_contact(&pic, canister_id, owner, details.clone()).unwrap().0.unwrap();
+        assert_eq!(jane.name, "Dr. Jane Roe", "The display name was not derived from the structured name.");
+        let john = call_create_contact(&pic, canister_id, owner, contact_details("John Doe", "", "")).unwrap().0.unwrap();
+        assert!(
+            john.structured_name.given == "John" && john.structured_name.family == "Doe",
+            "The structured name was not derived from the display name."
+        );
+
+        // Test labelled values. (Requirement 2)
+        assert!(
+            jane.emails == details.emails && jane.phones == details.phones && jane.addresses == details.addresses,
+            "Labelled values were not stored in order."
+        );
+        assert!(
+            jane.primary_email() == "jane@work.org" && jane.primary_phone() == "+15551234567",
+            "The first email and phone should be the primary ones."
+        );
+
+        // Test search over every value. (Requirement 3)
+        for query in ["home.net", "7654321", "acme"] {
+            let found = call_search_contacts(&pic, canister_id, owner, query).unwrap().0.unwrap();
+            assert!(
+                found.len() == 1 && found[0].id() == jane.id(),
+                "Searching for `{}` did not find the contact.", query
+            );
+        }
+
+        // Test round trips. (Requirement 4)
+        let same_details = |c: &contact::Contact| {
+            c.structured_name == jane.structured_name
+                && c.emails == jane.emails
+                && c.phones == jane.phones
+                && c.addresses == jane.addresses
+                && c.organization == jane.organization
+                && c.title == jane.title
+        };
+        let imported = |report: Vec<data::import_entry::ImportEntry>| match &report[..] {
+            [entry] => match &entry.outcome {
+                ImportOutcome::Imported(c) => Some(c.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        println!("Round-tripping through vCard...");
+        let other = Principal::from_slice(&[0x1f]);
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "vcard".to_string() });
+        let vcard = call_export_vcard(&pic, canister_id, owner, jane.id().unwrap(), VCardVersion::V3).unwrap().0.unwrap();
+        let report = call_import_vcards(&pic, canister_id, other, &vcard).unwrap().0.unwrap();
+        assert!(imported(report).is_some_and(|c| same_details(&c)), "Details were lost in a vCard round trip.");
+
+        println!("Round-tripping through jCard...");
+        let other = Principal::from_slice(&[0x20]);
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "jcard".to_string() });
+        let jcard = call_export_jcard(&pic, canister_id, owner, jane.id().unwrap()).unwrap().0.unwrap();
+        let report = call_import_jcards(&pic, canister_id, other, &jcard).unwrap().0.unwrap();
+        assert!(imported(report).is_some_and(|c| same_details(&c)), "Details were lost in a jCard round trip.");
+
+        println!("Round-tripping through Google CSV...");
+        let other = Principal::from_slice(&[0x21]);
+        let _ = call_create_account(&pic, canister_id, other, data::new_user::NewUser { username: "csv".to_string() });
+        let _ = call_delete_contact(&pic, canister_id, owner, john.id().unwrap());
+        let csv = call_export_csv(&pic, canister_id, owner, CsvLayout::Google).unwrap().0.unwrap();
+        let report = call_import_csv(&pic, canister_id, other, &csv).unwrap().0.unwrap();
+        assert!(imported(report).is_some_and(|c| same_details(&c)), "Details were lost in a CSV round trip.");
+    }
+
+    /// Testing validation of contact details.
+    /// The requirements are:
+    /// 1. Malformed emails are rejected against the offending field.
+    /// 2. Phone numbers are normalised to E.164, using the user's default country for national numbers.
+    /// 3. National numbers are rejected when the user has no default country, and unsupported countries cannot be set.
+    /// 4. Empty and overlong fields are rejected.
+    /// 5. Edits and imports are validated too.
+    #[test]
+    fn test_contact_validation() {
+        use data::import_entry::ImportOutcome;
+
+        let (pic, canister_id) = deploy_test_canister();
+
+        let owner = Principal::from_slice(&[0x22]);
+        let _ = call_create_account(&pic, canister_id, owner, data::new_user::NewUser { username: "owner".to_string() });
+
+        let rejected_field = |response: Result<(ContactsResult<contact::Contact>,), String>| match response {
+            Ok((Err(ContactsError::InvalidInput { field, .. }),)) => Some(field),
+            _ => None,
+        };
+
+        // Test email syntax. (Requirement 1)
+        println!("Creating contacts with malformed emails...");
+        for email in ["john", "john@localhost", "john..doe@example.com", "john@-example.com", "jo hn@example.com"] {
+            let response = call_create_contact(&pic, canister_id, owner, contact_details("John Doe", email, ""));
+            assert_eq!(rejected_field(response), Some("emails[0]".to_string()), "Email `{}` should be rejected.", email);
+        }
+        let john = call_create_contact(&pic, canister_id, owner, contact_details("John Doe", " John.Doe+x@Example.COM ", "")).unwrap().0.unwrap();
+        assert_eq!(john.primary_email(), "John.Doe+x@example.com", "Email was not trimmed with its domain lowercased.");
+
+        // Test national numbers without a default country. (Requirement 3)
+        println!("Creating a contact with a national number and no default country...");
+        let response = call_create_contact(&pic, canister_id, owner, contact_details("Jane Roe", "", "020 7946 0018"));
+        assert_eq!(rejected_field(response), Some("phones[0]".to_string()), "A national number needs a default country.");
+        let unsupported = call_set_default_country(&pic, canister_id, owner, Some("XX"));
+        assert!(
+            unsupported.is_ok_and(|response|
+                matches!(response.0, Err(ContactsError::InvalidInput { field, .. }) if field == "country")
+            ),
+            "An unsupported country should be rejected. Expected `InvalidInput`."
+        );
+
+        // Test E.164 normalisation. (Requirement 2)
+        println!("Normalising phone numbers...");
+        let _ = call_set_default_country(&pic, canister_id, owner, Some("gb"));
+        for (phone, expected) in [
+            ("020 7946 0018", "+442079460018"),
+            ("+44 (0)20 7946 0018", "+442079460018"),
+            ("0044 20 7946 0018", "+442079460018"),
+            ("+1 (555) 123-4567", "+15551234567"),
+        ] {
+            let contact = call_create_contact(&pic, canister_id, owner, contact_details("Jane Roe", "", phone)).unwrap().0;
+            assert!(
+                contact.as_ref().is_ok_and(|c| c.primary_phone() == expected),
+                "Phone `{}` should normalise to `{}`, got {:?}.", phone, expected, contact
+            );
+        }
+        for phone in ["555-CALL-NOW", "12", "+0 20 7946 0018", "+1234567890123456"] {
+            let response = call_create_contact(&pic, canister_id, owner, contact_details("Jane Roe", "", phone));
+            assert_eq!(rejected_field(response), Some("phones[0]".to_string()), "Phone `{}` should be rejected.", phone);
+        }
+
+        // Test empty and overlong fields. (Requirement 4)
+        println!("Creating contacts with empty and overlong fields...");
+        let response = call_create_contact(&pic, canister_id, owner, contact_details("  ", "", ""));
+        assert_eq!(rejected_field(response), Some("name".to_string()), "An empty name should be rejected.");
+        let response = call_create_contact(&pic, canister_id, owner, contact_details(&"a".repeat(201), "", ""));
+        assert_eq!(rejected_field(response), Some("name".to_string()), "An overlong name should be rejected.");
+        let mut details = contact_details("John Doe", "", "");
+        details.title = "t".repeat(201);
+        let response = call_create_contact(&pic, canister_id, owner, details);
+        assert_eq!(rejected_field(response), Some("title".to_string()), "An overlong title should be rejected.");
+
+        // Test edits and imports. (Requirement 5)
+        println!("Editing and importing invalid contacts...");
+        let response = call_edit_contact(&pic, canister_id, owner, john.id().unwrap(), contact_details("John Doe", "not an email", ""));
+        assert_eq!(rejected_field(response), Some("emails[0]".to_string()), "An invalid edit should be rejected.");
+        let vcards = "BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Bad Email\r\nEMAIL:bad@\r\nEND:VCARD\r\n\
+                      BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Good Phone\r\nTEL:07700 900123\r\nEND:VCARD\r\n";
+        let report = call_import_vcards(&pic, canister_id, owner, vcards).unwrap().0.unwrap();
+        assert!(
+            matches!(&report[0].outcome, ImportOutcome::Rejected(reason) if reason.starts_with("emails[0]"))
+                && matches!(&report[1].outcome, ImportOutcome::Imported(c) if c.primary_phone() == "+447700900123"),
+            "Imported cards should be validated individually."
+        );
+    }
+
+    /// Testing the username rules of create_account.
+    /// The requirements are:
+    /// 1. Usernames are unique regardless of case, surrounding whitespace and Unicode compatibility forms.
+    /// 2. Usernames must meet the length and character policy.
+    /// 3. Usernames mixing scripts or made of lookalike letters are rejected.
+    /// 4. Reserved usernames are rejected.
+    /// 5. Users are found by any spelling of their username.
+    #[test]
+    fn test_username_rules() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let alice = Principal::from_slice(&[0x23]);
+        let other = Principal::from_slice(&[0x24]);
+        let create = |principal: Principal, username: &str| {
+            call_create_account(&pic, canister_id, principal, data::new_user::NewUser { username: username.to_string() })
+                .unwrap()
+                .0
+        };
+
+        // Test case-insensitive uniqueness. (Requirement 1)
+        println!("Creating accounts with different spellings of the same username...");
+        assert!(create(alice, " Alice ").is_ok(), "A valid username should be accepted.");
+        assert_eq!(
+            call_whoami(&pic, canister_id, alice).unwrap().1,
+            Some("Alice".to_string()),
+            "The username should be stored trimmed with its case preserved."
+        );
+        for spelling in ["alice", "ALICE", "\u{ff21}lice"] {
+            assert!(
+                matches!(create(other, spelling), Err(ContactsError::Conflict(_))),
+                "Username `{}` should clash with `Alice`. Expected `Conflict`.", spelling
+            );
+        }
+
+        // Test the length and character policy. (Requirement 2)
+        println!("Creating accounts with invalid usernames...");
+        let invalid = |username: &str| matches!(create(other, username), Err(ContactsError::InvalidInput { field, .. }) if field == "username");
+        for username in ["", "ab", &"a".repeat(33), "bad name", "bad@name", "_bad", "bad-", "bad..name"] {
+            assert!(invalid(username), "Username `{}` should be rejected. Expected `InvalidInput`.", username);
+        }
+
+        // Test confusable usernames. (Requirement 3)
+        println!("Creating accounts with confusable usernames...");
+        for username in ["p\u{430}ypal", "\u{440}\u{430}\u{443}\u{440}\u{430}"] {
+            assert!(invalid(username), "Username `{}` should be rejected as confusable. Expected `InvalidInput`.", username);
+        }
+
+        // Test reserved usernames. (Requirement 4)
+        println!("Creating accounts with reserved usernames...");
+        for username in ["admin", "Root", "ad-min"] {
+            assert!(invalid(username), "Username `{}` should be reserved. Expected `InvalidInput`.", username);
+        }
+        assert!(
+            create(other, "\u{414}\u{43c}\u{438}\u{442}\u{440}\u{438}\u{439}").is_ok(),
+            "A username in a single non-Latin script should be accepted."
+        );
+
+        // Test looking users up by another spelling. (Requirement 5)
+        println!("Sharing a contact using a different spelling of the username...");
+        let contact = call_create_contact(&pic, canister_id, other, contact_details("John Doe", "", "")).unwrap().0.unwrap();
+        let shared = call_share_contact(&pic, canister_id, other, contact.id().unwrap(), "ALICE");
+        assert!(
+            shared.is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Sharing should find the user regardless of case."
+        );
+    }
+
+    /// Testing the change_username function and its adherence to the requirements.
+    /// The requirements are:
+    /// 1. A user can change their username, and is then found by the new one.
+    /// 2. The new username follows the same rules and uniqueness checks as `create_account`.
+    /// 3. An old username kept reserved still resolves to its former owner and cannot be claimed until the grace period ends.
+    /// 4. An old username that is not kept reserved is free immediately.
+    /// 5. A user without an account cannot change their username.
+    #[test]
+    fn test_change_username() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let alice = Principal::from_slice(&[0x25]);
+        let bob = Principal::from_slice(&[0x26]);
+        let stranger = Principal::from_slice(&[0x27]);
+        let _ = call_create_account(&pic, canister_id, alice, data::new_user::NewUser { username: "alcie".to_string() });
+        let _ = call_create_account(&pic, canister_id, bob, data::new_user::NewUser { username: "bob".to_string() });
+        let contact = call_create_contact(&pic, canister_id, bob, contact_details("John Doe", "", "")).unwrap().0.unwrap();
+        let change = |principal: Principal, username: &str, keep_old_reserved: bool| {
+            call_change_username(&pic, canister_id, principal, username, keep_old_reserved).unwrap().0
+        };
+
+        // Test the naming rules. (Requirement 2)
+        println!("Changing to invalid and taken usernames...");
+        assert!(
+            matches!(change(alice, "admin", false), Err(ContactsError::InvalidInput { .. })),
+            "A reserved username should be rejected. Expected `InvalidInput`."
+        );
+        assert!(
+            matches!(change(alice, "BOB", false), Err(ContactsError::Conflict(_))),
+            "A taken username should be rejected. Expected `Conflict`."
+        );
+
+        // Test changing the username while keeping the old one reserved. (Requirements 1 and 3)
+        println!("Fixing a typo in a username...");
+        assert!(change(alice, "Alice", true).is_ok(), "Changing to a free username should succeed.");
+        assert_eq!(call_whoami(&pic, canister_id, alice).unwrap().1, Some("Alice".to_string()), "The username was not changed.");
+        assert!(
+            call_share_contact(&pic, canister_id, bob, contact.id().unwrap(), "alice").is_ok_and(|response| matches!(response.0, Ok(_))),
+            "The user should be found by their new username."
+        );
+        assert!(
+            call_revoke_shared_contact(&pic, canister_id, bob, contact.id().unwrap(), "alcie").is_ok_and(|response| matches!(response.0, Ok(_))),
+            "A reserved old username should still resolve to its former owner."
+        );
+        assert!(
+            matches!(change(bob, "alcie", false), Err(ContactsError::Conflict(_))),
+            "A reserved old username should not be claimable by others. Expected `Conflict`."
+        );
+        assert!(change(alice, "ALICE", false).is_ok(), "Changing only the case of a username should succeed.");
+
+        pic.advance_time(Duration::from_secs(31 * 24 * 60 * 60));
+        assert!(
+            call_share_contact(&pic, canister_id, bob, contact.id().unwrap(), "alcie").is_ok_and(|response| matches!(response.0, Err(ContactsError::NotFound))),
+            "An old username should stop resolving after the grace period. Expected `NotFound`."
+        );
+        assert!(change(bob, "alcie", false).is_ok(), "An expired reservation should not block others.");
+
+        // Test changing the username without keeping the old one. (Requirement 4)
+        println!("Changing a username without keeping the old one...");
+        assert!(change(alice, "alice2", false).is_ok(), "Changing to a free username should succeed.");
+        let _ = call_create_account(&pic, canister_id, stranger, data::new_user::NewUser { username: "Alice".to_string() });
+        assert_eq!(
+            call_whoami(&pic, canister_id, stranger).unwrap().1,
+            Some("Alice".to_string()),
+            "An old username that was not kept reserved should be free immediately."
+        );
+
+        // Test changing the username without an account. (Requirement 5)
+        let unregistered = call_change_username(&pic, canister_id, Principal::from_slice(&[0x28]), "nobody", false);
+        assert!(
+            unregistered.is_err(),
+            "A user without an account should not be able to change their username. Expected it to be dropped by inspect_message."
+        );
+    }
+
+    /// Testing that moving a username never takes over another account's entry.
+    /// The requirements are:
+    /// 1. A legacy user stored under their exact spelling cannot move onto a folded key held by another account.
+    /// 2. A user holding their folded key can change only its case.
+    /// 3. A legacy user can move to a free key, releasing their exact spelling.
+    #[test]
+    fn test_move_legacy_username() {
+        let holder = Principal::from_slice(&[0x32]);
+        let legacy = Principal::from_slice(&[0x33]);
+        let key = |username: &str| crate::username::username_key(username);
+        let lookup = |key: &str| crate::USERNAME_MAP.with(|p| p.borrow().get(&key.to_string()));
+        // "Alice" was stored as written because "alice" already belonged to another account
+        crate::USERNAME_MAP.with(|p| {
+            let mut usernames = p.borrow_mut();
+            usernames.insert("alice".to_string(), holder);
+            usernames.insert("Alice".to_string(), legacy);
+        });
+
+        // Test moving a legacy username onto a taken key. (Requirement 1)
+        assert!(
+            matches!(crate::move_username(legacy, "Alice", &key("alice"), 0), Err(ContactsError::Conflict(_))),
+            "A legacy user should not take over another account's key. Expected `Conflict`."
+        );
+        assert_eq!(lookup("alice"), Some(holder), "The key should still belong to its holder.");
+        assert_eq!(lookup("Alice"), Some(legacy), "The legacy entry should be untouched.");
+
+        // Test changing only the case of a held key. (Requirement 2)
+        assert_eq!(
+            crate::move_username(holder, "alice", &key("ALICE"), 0),
+            Ok(Some("alice".to_string())),
+            "Changing only the case of a held username should succeed."
+        );
+        assert_eq!(lookup("alice"), Some(holder), "The key should still belong to its holder.");
+
+        // Test moving a legacy username to a free key. (Requirement 3)
+        assert_eq!(
+            crate::move_username(legacy, "Alice", &key("Alice2"), 0),
+            Ok(Some("Alice".to_string())),
+            "A legacy user should be able to move to a free username."
+        );
+        assert_eq!(lookup("alice2"), Some(legacy), "The new key was not assigned.");
+        assert_eq!(lookup("Alice"), None, "The legacy entry should be released.");
+        assert_eq!(lookup("alice"), Some(holder), "Other accounts' keys should be untouched.");
+    }
+
+    /// Testing that link requests can be found by the account that made them.
+    /// The requirements are:
+    /// 1. A link request is indexed under its account only.
+    /// 2. Pruning drops an account's expired link requests and counts its pending ones.
+    /// 3. Withdrawing a link request removes it from both the requests and the index.
+    #[test]
+    fn test_link_request_index() {
+        let account = Principal::from_slice(&[0x36]);
+        let other = Principal::from_slice(&[0x37]);
+        let phone = Principal::from_slice(&[0x38]);
+        let tablet = Principal::from_slice(&[0x39]);
+        let request = |account: Principal, expires_at: u64| Some(data::link_request::LinkRequest { account, expires_at });
+        let requested = |principal: Principal, account: Principal| {
+            crate::LINK_REQUEST_MAP.with(|p| p.borrow().contains_key(&data::link_request_key::LinkRequestKey { principal, account }))
+        };
+        crate::set_link_request(phone, account, request(account, 10));
+        crate::set_link_request(tablet, account, request(account, 20));
+        crate::set_link_request(phone, other, request(other, 10));
+
+        // Test indexing link requests by account. (Requirement 1)
+        assert_eq!(crate::requested_principals(account), vec![phone, tablet], "The account's link requests were not indexed.");
+        assert_eq!(crate::requested_principals(other), vec![phone], "Other accounts' link requests should be indexed separately.");
+
+        // Test pruning expired link requests. (Requirement 2)
+        assert_eq!(crate::prune_link_requests(account, 15), 1, "Only the active link request should be counted.");
+        assert_eq!(crate::requested_principals(account), vec![tablet], "The expired link request should be pruned.");
+        assert!(!requested(phone, account), "The expired link request should be removed.");
+        assert!(requested(phone, other), "Other accounts' link requests should be untouched.");
+
+        // Test withdrawing a link request. (Requirement 3)
+        crate::set_link_request(tablet, account, None);
+        assert!(crate::requested_principals(account).is_empty(), "The index should be empty for the account.");
+        assert!(!requested(tablet, account), "The link request should be removed.");
+    }
+
+    /// Testing that retired usernames can be found by the account that retired them.
+    /// The requirements are:
+    /// 1. Retiring a username indexes it under its former owner only.
+    /// 2. Pruning drops an account's expired reservations and keeps its active ones.
+    /// 3. Releasing every retired username of an account clears both the map and the index.
+    #[test]
+    fn test_retired_username_index() {
+        let owner = Principal::from_slice(&[0x34]);
+        let other = Principal::from_slice(&[0x35]);
+        let reserved = |key: &str| crate::RETIRED_USERNAME_MAP.with(|p| p.borrow().contains_key(&key.to_string()));
+        crate::retire_username("bob".to_string(), owner, 10);
+        crate::retire_username("bobby".to_string(), owner, 20);
+        crate::retire_username("carol".to_string(), other, 10);
+
+        // Test indexing retired usernames by owner. (Requirement 1)
+        assert_eq!(
+            crate::retired_usernames(owner),
+            vec!["bob".to_string(), "bobby".to_string()],
+            "The owner's retired usernames were not indexed."
+        );
+        assert_eq!(crate::retired_usernames(other), vec!["carol".to_string()], "Other accounts' usernames should be indexed separately.");
+
+        // Test pruning expired reservations. (Requirement 2)
+        crate::prune_retired_usernames(owner, 15);
+        assert_eq!(crate::retired_usernames(owner), vec!["bobby".to_string()], "The expired reservation should be pruned.");
+        assert!(!reserved("bob"), "The expired reservation should be removed from the map.");
+        assert!(reserved("carol"), "Other accounts' reservations should be untouched.");
+
+        // Test releasing every retired username of an account. (Requirement 3)
+        for key in crate::retired_usernames(owner) {
+            crate::release_retired_username(&key);
+        }
+        assert!(crate::retired_usernames(owner).is_empty(), "The index should be empty for the account.");
+        assert!(!reserved("bobby"), "The reservation should be removed from the map.");
+        assert!(reserved("carol"), "Other accounts' reservations should be untouched.");
+    }
+
+    /// Testing the delete_account function and its adherence to the requirements.
+    /// The requirements are:
+    /// 1. Deleting an account removes the user and frees their username immediately.
+    /// 2. All of the user's contacts are removed, even when there are too many for one message.
+    /// 3. Contacts the user shared are withdrawn from their recipients.
+    /// 4. Contacts shared with the user by others are left untouched.
+    /// 5. A user without an account cannot delete it.
+    #[test]
+    fn test_delete_account() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let alice = Principal::from_slice(&[0x29]);
+        let bob = Principal::from_slice(&[0x2a]);
+        let _ = call_create_account(&pic, canister_id, alice, data::new_user::NewUser { username: "alice".to_string() });
+        let _ = call_create_account(&pic, canister_id, bob, data::new_user::NewUser { username: "bob".to_string() });
+
+        // Fill a large address book and share contacts both ways.
+        println!("Creating a large address book...");
+        let vcards: String = (0..250)
+            .map(|i| format!("BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Person {}\r\nEND:VCARD\r\n", i))
+            .collect();
+        let _ = call_import_vcards(&pic, canister_id, alice, &vcards);
+        let alices_contact = call_create_contact(&pic, canister_id, alice, contact_details("Shared By Alice", "", "")).unwrap().0.unwrap();
+        let bobs_contact = call_create_contact(&pic, canister_id, bob, contact_details("Shared By Bob", "", "")).unwrap().0.unwrap();
+        let _ = call_share_contact(&pic, canister_id, alice, alices_contact.id().unwrap(), "bob");
+        let _ = call_share_contact(&pic, canister_id, bob, bobs_contact.id().unwrap(), "alice");
+        assert_eq!(
+            call_get_contacts(&pic, canister_id, alice).unwrap().0.map(|contacts| contacts.len()),
+            Ok(252),
+            "The address book was not filled."
+        );
+
+        // Test deleting the account. (Requirement 1)
+        println!("Deleting the account...");
+        assert!(
+            call_delete_account(&pic, canister_id, alice).is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Deleting an account should succeed."
+        );
+        assert_eq!(call_whoami(&pic, canister_id, alice).unwrap().1, None, "The user should be gone.");
+        assert!(
+            query::<(ContactsResult<Vec<data::contact::Contact>>,)>(&pic, alice, canister_id, "get_contacts", encode_one(()).unwrap())
+                .is_ok_and(|response| matches!(response.0, Err(ContactsError::NotRegistered))),
+            "A deleted user should no longer be registered. Expected `NotRegistered`."
+        );
+
+        // let the remaining batches run
+        for _ in 0..10 {
+            pic.tick();
+        }
+
+        // Test that the contacts are gone. (Requirements 2 and 3)
+        println!("Checking the deleted contacts...");
+        assert!(
+            call_get_shared_contacts(&pic, canister_id, bob).is_ok_and(|response| matches!(response.0, Ok(shared) if shared.is_empty())),
+            "Contacts shared by a deleted user should be withdrawn."
+        );
+        let _ = call_create_account(&pic, canister_id, alice, data::new_user::NewUser { username: "alice".to_string() });
+        assert_eq!(
+            call_whoami(&pic, canister_id, alice).unwrap().1,
+            Some("alice".to_string()),
+            "The username of a deleted account should be free."
+        );
+        assert_eq!(
+            call_get_contacts(&pic, canister_id, alice).unwrap().0.map(|contacts| contacts.len()),
+            Ok(0),
+            "A re-created account should not see the deleted contacts."
+        );
+
+        // Test that others' contacts survive. (Requirement 4)
+        assert_eq!(
+            call_get_contacts(&pic, canister_id, bob).unwrap().0.map(|contacts| contacts.len()),
+            Ok(1),
+            "Contacts of other users should be left untouched."
+        );
+
+        // Test deleting without an account. (Requirement 5)
+        let unregistered = call_delete_account(&pic, canister_id, Principal::from_slice(&[0x2b]));
+        assert!(
+            unregistered.is_err(),
+            "A user without an account should not be able to delete it. Expected it to be dropped by inspect_message."
+        );
+    }
+
+    /// Testing account linking and its adherence to the requirements.
+    /// The requirements are:
+    /// 1. A principal linked to an account acts on that account's data.
+    /// 2. A link only takes effect once the linked principal confirms it with the account's username.
+    /// 3. A principal with its own account cannot be linked.
+    /// 4. Linked principals can be listed and unlinked, but the principal that created the account cannot be unlinked.
+    /// 5. A link request expires.
+    /// 6. A principal can hold link requests from several accounts and confirms the one it names.
+    /// 7. An account can only have `MAX_PENDING_LINK_REQUESTS` link requests pending at a time.
+    #[test]
+    fn test_link_principals() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let laptop = Principal::from_slice(&[0x2c]);
+        let phone = Principal::from_slice(&[0x2d]);
+        let bob = Principal::from_slice(&[0x2e]);
+        let tablet = Principal::from_slice(&[0x2f]);
+        let _ = call_create_account(&pic, canister_id, laptop, data::new_user::NewUser { username: "alice".to_string() });
+        let _ = call_create_account(&pic, canister_id, bob, data::new_user::NewUser { username: "bob".to_string() });
+        let _ = call_create_contact(&pic, canister_id, laptop, contact_details("John Doe", "", ""));
+
+        // Test that a link must be confirmed with the right username. (Requirement 2)
+        println!("Linking a second device...");
+        assert!(
+            call_request_link(&pic, canister_id, laptop, phone).is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Requesting a link should succeed."
+        );
+        assert_eq!(call_whoami(&pic, canister_id, phone).unwrap().1, None, "A link should not take effect before it is confirmed.");
+        assert!(
+            call_confirm_link(&pic, canister_id, phone, "bob").is_ok_and(|response| matches!(response.0, Err(ContactsError::NotFound))),
+            "Confirming a link to another account should fail. Expected `NotFound`."
+        );
+        assert!(
+            call_confirm_link(&pic, canister_id, phone, "Alice").is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Confirming a link with the account's username should succeed."
+        );
+
+        // Test that the linked principal acts on the account. (Requirement 1)
+        assert_eq!(call_whoami(&pic, canister_id, phone).unwrap(), (laptop, Some("alice".to_string())), "The device was not linked.");
+        let _ = call_create_contact(&pic, canister_id, phone, contact_details("Jane Doe", "", ""));
+        assert_eq!(
+            call_get_contacts(&pic, canister_id, laptop).unwrap().0.map(|contacts| contacts.len()),
+            Ok(2),
+            "Contacts created from a linked device should belong to the account."
+        );
+
+        // Test that principals with an account cannot be linked. (Requirement 3)
+        assert!(
+            call_request_link(&pic, canister_id, laptop, bob).is_ok_and(|response| matches!(response.0, Err(ContactsError::Conflict(_)))),
+            "A principal with its own account should not be linkable. Expected `Conflict`."
+        );
+        assert!(
+            call_create_account(&pic, canister_id, phone, data::new_user::NewUser { username: "phone".to_string() })
+                .is_ok_and(|response| matches!(response.0, Err(ContactsError::Conflict(_)))),
+            "A linked principal should not be able to create another account. Expected `Conflict`."
+        );
+
+        // Test listing and unlinking principals. (Requirement 4)
+        println!("Listing and unlinking devices...");
+        let principals = call_get_linked_principals(&pic, canister_id, phone).unwrap().0.unwrap();
+        assert_eq!(
+            principals.iter().map(|p| (p.principal, p.linked_at.is_some(), p.is_caller)).collect::<Vec<_>>(),
+            vec![(laptop, false, false), (phone, true, true)],
+            "The linked principals were not listed."
+        );
+        assert!(
+            call_unlink_principal(&pic, canister_id, phone, laptop).is_ok_and(|response| matches!(response.0, Err(ContactsError::InvalidInput { .. }))),
+            "The principal that created the account should not be unlinkable. Expected `InvalidInput`."
+        );
+        assert!(
+            call_unlink_principal(&pic, canister_id, laptop, phone).is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Unlinking a device should succeed."
+        );
+        assert_eq!(call_whoami(&pic, canister_id, phone).unwrap(), (phone, None), "An unlinked device should sign in as a new user.");
+        assert!(
+            call_unlink_principal(&pic, canister_id, laptop, phone).is_ok_and(|response| matches!(response.0, Err(ContactsError::NotFound))),
+            "Unlinking a device that is not linked should fail. Expected `NotFound`."
+        );
+
+        // Test link requests from several accounts. (Requirement 6)
+        println!("Requesting links from several accounts...");
+        let _ = call_request_link(&pic, canister_id, laptop, tablet);
+        assert!(
+            call_request_link(&pic, canister_id, bob, tablet).is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Another account should be able to request a link to the same principal."
+        );
+        assert!(
+            call_confirm_link(&pic, canister_id, tablet, "bob").is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Confirming the request of the named account should succeed."
+        );
+        assert_eq!(call_whoami(&pic, canister_id, tablet).unwrap(), (bob, Some("bob".to_string())), "The device was not linked to the named account.");
+
+        // Test that link requests expire. (Requirement 5)
+        let _ = call_request_link(&pic, canister_id, laptop, phone);
+        pic.advance_time(Duration::from_secs(2 * 60 * 60));
+        assert!(
+            call_confirm_link(&pic, canister_id, phone, "alice").is_ok_and(|response| matches!(response.0, Err(ContactsError::NotFound))),
+            "An expired link request should not be confirmable. Expected `NotFound`."
+        );
+
+        // Test the limit on pending link requests. (Requirement 7)
+        println!("Requesting too many links...");
+        for n in 0..10 {
+            assert!(
+                call_request_link(&pic, canister_id, laptop, Principal::from_slice(&[0x40, n])).is_ok_and(|response| matches!(response.0, Ok(_))),
+                "Requesting links up to the limit should succeed."
+            );
+        }
+        assert!(
+            call_request_link(&pic, canister_id, laptop, phone).is_ok_and(|response| matches!(response.0, Err(ContactsError::QuotaExceeded))),
+            "Requesting links past the limit should fail. Expected `QuotaExceeded`."
+        );
+        assert!(
+            call_request_link(&pic, canister_id, laptop, Principal::from_slice(&[0x40, 0])).is_ok_and(|response| matches!(response.0, Ok(_))),
+            "Renewing a pending request should not count against the limit."
+        );
+    }
+
+    /// Testing that the anonymous principal is rejected.
+    /// The requirements are:
+    /// 1. Update calls from the anonymous principal are dropped before they are executed.
+    /// 2. Private queries from the anonymous principal fail with `AnonymousCaller`.
+    /// 3. The anonymous principal cannot create an account or claim a username.
+    #[test]
+    fn test_anonymous_caller() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let anonymous = Principal::anonymous();
+
+        // Test that update calls are dropped. (Requirements 1 and 3)
+        println!("Calling updates anonymously...");
+        assert!(
+            call_create_account(&pic, canister_id, anonymous, data::new_user::NewUser { username: "visitor".to_string() }).is_err(),
+            "An anonymous create_account should be dropped by inspect_message."
+        );
+        assert!(
+            call_create_contact(&pic, canister_id, anonymous, contact_details("John Doe", "", "")).is_err(),
+            "An anonymous create_contact should be dropped by inspect_message."
+        );
+
+        // Test that private queries are rejected. (Requirement 2)
+        println!("Calling queries anonymously...");
+        let contacts = query::<(ContactsResult<Vec<data::contact::Contact>>,)>(&pic, anonymous, canister_id, "get_contacts", encode_one(()).unwrap());
+        assert!(
+            contacts.is_ok_and(|response| matches!(response.0, Err(ContactsError::AnonymousCaller))),
+            "An anonymous private query should be rejected. Expected `AnonymousCaller`."
+        );
+        let country = query::<(ContactsResult<Option<String>>,)>(&pic, anonymous, canister_id, "get_default_country", encode_one(()).unwrap());
+        assert!(
+            country.is_ok_and(|response| matches!(response.0, Err(ContactsError::AnonymousCaller))),
+            "An anonymous private query should be rejected. Expected `AnonymousCaller`."
+        );
+
+        // Test that the username was not claimed. (Requirement 3)
+        let visitor = Principal::from_slice(&[0x30]);
+        assert!(
+            call_create_account(&pic, canister_id, visitor, data::new_user::NewUser { username: "visitor".to_string() })
+                .is_ok_and(|response| matches!(response.0, Ok(_))),
+            "The anonymous principal should not have claimed a username."
+        );
+        assert_eq!(
+            query::<(Principal, Option<String>)>(&pic, anonymous, canister_id, "whoami", encode_one(()).unwrap()),
+            Ok((anonymous, None)),
+            "The anonymous principal should never have an account."
+        );
+    }
+
+    /// Testing the ingress filtering of inspect_message.
+    /// The requirements are:
+    /// 1. Calls to unknown methods are dropped.
+    /// 2. Calls from callers without an account to methods that need one are dropped, but they can still create an account.
+    /// 3. Calls with oversized arguments are dropped.
+    /// 4. Calls from registered users within the limits are accepted.
+    #[test]
+    fn test_inspect_message() {
+        let (pic, canister_id) = deploy_test_canister();
+
+        let alice = Principal::from_slice(&[0x31]);
+
+        // Test calling an unknown method. (Requirement 1)
+        println!("Calling an unknown method...");
+        assert!(
+            update::<()>(&pic, alice, canister_id, "drop_all_contacts", encode_one(()).unwrap()).is_err(),
+            "A call to an unknown method should be dropped."
+        );
+
+        // Test calling without an account. (Requirement 2)
+        println!("Calling without an account...");
+        assert!(
+            call_get_contacts(&pic, canister_id, alice).is_err(),
+            "A call from a caller without an account should be dropped."
+        );
+        assert!(
+            call_create_account(&pic, canister_id, alice, data::new_user::NewUser { username: "alice".to_string() })
+                .is_ok_and(|response| matches!(response.0, Ok(_))),
+            "A caller without an account should be able to create one."
+        );
+
+        // Test oversized arguments. (Requirement 3)
+        println!("Sending oversized arguments...");
+        assert!(
+            call_create_contact(&pic, canister_id, alice, contact_details(&"a".repeat(100 * 1024), "", "")).is_err(),
+            "A contact with oversized arguments should be dropped."
+        );
+        assert!(
+            call_share_contact(&pic, canister_id, alice, 0, &"b".repeat(2048)).is_err(),
+            "A share with an oversized username should be dropped."
+        );
+
+        // Test calls within the limits. (Requirement 4)
+        assert!(
+            call_create_contact(&pic, canister_id, alice, contact_details("John Doe", "", "")).is_ok_and(|response| matches!(response.0, Ok(_))),
+            "A registered user should be able to create a contact."
+        );
+        assert!(
+            call_get_contacts(&pic, canister_id, alice).is_ok_and(|response| matches!(response.0, Ok(contacts) if contacts.len() == 1)),
+            "A registered user should be able to get their contacts."
+        );
+    }
 }
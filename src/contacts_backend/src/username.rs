@@ -0,0 +1,139 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Length limits of a username, in characters after normalization.
+const MIN_USERNAME_LEN: usize = 3;
+const MAX_USERNAME_LEN: usize = 32;
+
+/// Characters allowed between the letters and digits of a username.
+const SEPARATORS: [char; 3] = ['_', '-', '.'];
+
+/// Names that could be mistaken for the service or its staff. They are compared against the
+/// case-folded username with separators removed, so "Ad-Min" is reserved too.
+const RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "anonymous",
+    "api",
+    "canister",
+    "contacts",
+    "everyone",
+    "help",
+    "moderator",
+    "null",
+    "official",
+    "root",
+    "security",
+    "staff",
+    "support",
+    "system",
+    "undefined",
+];
+
+/// Cyrillic and Greek lowercase letters that look like Latin letters. A name written only
+/// with these could pass for a Latin name, e.g. Cyrillic "аре" for "ape".
+const LATIN_LOOKALIKES: &str = "аеіјорсуховԁԛԝһӏѕαιοκνρυχ";
+
+/// A username accepted by `parse_username`.
+pub struct Username {
+    /// The name as it is shown to users: NFKC-normalised, with its case preserved.
+    pub display: String,
+    /// The name under which it is unique, from `username_key`.
+    pub key: String,
+}
+
+/// The canonical form of a username: NFKC-normalised and case-folded, so that "Alice",
+/// "alice" and "ａｌｉｃｅ" share a key. Usernames are stored and looked up by this key.
+pub fn username_key(username: &str) -> String {
+    let normalized: String = username.trim().nfkc().collect();
+    normalized.to_lowercase().nfkc().collect()
+}
+
+/// Check a requested username against the naming rules, returning its display form and key,
+/// or the reason it was rejected.
+///
+/// A username is 3 to 32 letters and digits, optionally separated by single underscores,
+/// dashes or dots. Its letters must all come from one script, and it must not be reserved.
+pub fn parse_username(username: &str) -> Result<Username, String> {
+    let display: String = username.trim().nfkc().collect();
+    let length = display.chars().count();
+    if !(MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&length) {
+        return Err(format!(
+            "must be {} to {} characters long",
+            MIN_USERNAME_LEN, MAX_USERNAME_LEN
+        ));
+    }
+    if let Some(c) = display.chars().find(|&c| !c.is_alphanumeric() && !SEPARATORS.contains(&c)) {
+        return Err(format!("must not contain '{}'", c));
+    }
+    if let Some(c) = display.chars().find(|&c| c.is_numeric() && !c.is_ascii_digit()) {
+        return Err(format!("must only use the digits 0-9, not '{}'", c));
+    }
+    let starts_or_ends_with_separator =
+        [display.chars().next(), display.chars().last()].iter().flatten().any(|c| SEPARATORS.contains(c));
+    if starts_or_ends_with_separator {
+        return Err("must start and end with a letter or digit".to_string());
+    }
+    let doubled_separator = display
+        .chars()
+        .zip(display.chars().skip(1))
+        .any(|(a, b)| SEPARATORS.contains(&a) && SEPARATORS.contains(&b));
+    if doubled_separator {
+        return Err("must not have two separators in a row".to_string());
+    }
+
+    let key = username_key(&display);
+    check_confusables(&key)?;
+    let bare: String = key.chars().filter(|c| !SEPARATORS.contains(c)).collect();
+    if RESERVED_USERNAMES.contains(&bare.as_str()) {
+        return Err("is reserved".to_string());
+    }
+    Ok(Username { display, key })
+}
+
+/// Writing systems that are told apart to catch lookalike usernames.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Cjk, // Han and kana, which Japanese mixes freely
+    Hangul,
+    Other,
+}
+
+fn script_of(c: char) -> Script {
+    match c as u32 {
+        0x0041..=0x024F | 0x1E00..=0x1EFF => Script::Latin,
+        0x0370..=0x03FF | 0x1F00..=0x1FFF => Script::Greek,
+        0x0400..=0x052F => Script::Cyrillic,
+        0x0530..=0x058F => Script::Armenian,
+        0x0590..=0x05FF => Script::Hebrew,
+        0x0600..=0x06FF => Script::Arabic,
+        0x3040..=0x30FF | 0x4E00..=0x9FFF => Script::Cjk,
+        0xAC00..=0xD7AF => Script::Hangul,
+        _ => Script::Other,
+    }
+}
+
+/// Reject usernames that mix scripts, such as a Latin name with a Cyrillic "а", and
+/// non-Latin names made only of letters that look Latin.
+fn check_confusables(key: &str) -> Result<(), String> {
+    let mut scripts = key.chars().filter(|c| c.is_alphabetic()).map(script_of);
+    let Some(first) = scripts.next() else {
+        return Ok(());
+    };
+    if scripts.any(|script| script != first) {
+        return Err("must not mix letters from different scripts".to_string());
+    }
+    let letters_look_latin = key
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .all(|c| LATIN_LOOKALIKES.contains(c));
+    if matches!(first, Script::Greek | Script::Cyrillic) && letters_look_latin {
+        return Err("must not be made only of letters that look like Latin letters".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,337 @@
+use crate::data::labelled_value::LabelledValue;
+use crate::data::new_contact::NewContact;
+use crate::data::postal_address::PostalAddress;
+use crate::data::structured_name::StructuredName;
+use crate::response::error::ContactsError;
+
+/// Longest accepted value of each contact field, in characters.
+const MAX_NAME_LEN: usize = 200;
+const MAX_NAME_PART_LEN: usize = 100;
+const MAX_LABEL_LEN: usize = 32;
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_EMAIL_LOCAL_PART_LEN: usize = 64;
+const MAX_DOMAIN_LABEL_LEN: usize = 63;
+const MAX_PHONE_LEN: usize = 32;
+const MAX_ORGANIZATION_LEN: usize = 200;
+const MAX_TITLE_LEN: usize = 200;
+const MAX_ADDRESS_PART_LEN: usize = 200;
+
+/// Most emails, phones or addresses a single contact may have.
+const MAX_VALUES_PER_FIELD: usize = 20;
+
+/// Digits of an E.164 number, including the country code.
+const MIN_E164_DIGITS: usize = 7;
+const MAX_E164_DIGITS: usize = 15;
+
+/// Fewest digits of a national number, without trunk prefix or country code.
+const MIN_NATIONAL_DIGITS: usize = 4;
+
+/// Characters allowed in the local part of an email address besides letters and digits.
+const EMAIL_LOCAL_PART_SYMBOLS: &str = "!#$%&'*+/=?^_`{|}~.-";
+
+/// Countries that can be used as the default for phone numbers written without a country
+/// code: ISO 3166-1 alpha-2 code, calling code, and the trunk prefix dialled before national
+/// numbers, if any.
+const COUNTRIES: &[(&str, &str, &str)] = &[
+    ("AE", "971", "0"),
+    ("AR", "54", "0"),
+    ("AT", "43", "0"),
+    ("AU", "61", "0"),
+    ("BE", "32", "0"),
+    ("BG", "359", "0"),
+    ("BR", "55", "0"),
+    ("CA", "1", "1"),
+    ("CH", "41", "0"),
+    ("CL", "56", ""),
+    ("CN", "86", "0"),
+    ("CO", "57", ""),
+    ("CZ", "420", ""),
+    ("DE", "49", "0"),
+    ("DK", "45", ""),
+    ("EE", "372", ""),
+    ("EG", "20", "0"),
+    ("ES", "34", ""),
+    ("FI", "358", "0"),
+    ("FR", "33", "0"),
+    ("GB", "44", "0"),
+    ("GR", "30", ""),
+    ("HK", "852", ""),
+    ("HR", "385", "0"),
+    ("HU", "36", "06"),
+    ("ID", "62", "0"),
+    ("IE", "353", "0"),
+    ("IL", "972", "0"),
+    ("IN", "91", "0"),
+    ("IS", "354", ""),
+    ("IT", "39", ""),
+    ("JP", "81", "0"),
+    ("KE", "254", "0"),
+    ("KR", "82", "0"),
+    ("KZ", "7", "8"),
+    ("LT", "370", "8"),
+    ("LU", "352", ""),
+    ("LV", "371", ""),
+    ("MA", "212", "0"),
+    ("MX", "52", ""),
+    ("MY", "60", "0"),
+    ("NG", "234", "0"),
+    ("NL", "31", "0"),
+    ("NO", "47", ""),
+    ("NZ", "64", "0"),
+    ("PE", "51", "0"),
+    ("PH", "63", "0"),
+    ("PK", "92", "0"),
+    ("PL", "48", ""),
+    ("PT", "351", ""),
+    ("RO", "40", "0"),
+    ("RS", "381", "0"),
+    ("RU", "7", "8"),
+    ("SA", "966", "0"),
+    ("SE", "46", "0"),
+    ("SG", "65", ""),
+    ("SI", "386", "0"),
+    ("SK", "421", "0"),
+    ("TH", "66", "0"),
+    ("TR", "90", "0"),
+    ("TW", "886", "0"),
+    ("UA", "380", "0"),
+    ("US", "1", "1"),
+    ("VN", "84", "0"),
+    ("ZA", "27", "0"),
+];
+
+fn invalid(field: impl Into<String>, reason: impl Into<String>) -> ContactsError {
+    ContactsError::InvalidInput {
+        field: field.into(),
+        reason: reason.into(),
+    }
+}
+
+/// Check contact details supplied by a client and return them normalised: text trimmed,
+/// email domains lowercased and phone numbers in E.164 form. Numbers written without a
+/// country code are read as national numbers of `default_country`.
+///
+/// The first problem found is reported against the offending field, e.g. `emails[1]`.
+pub fn validate_contact(details: NewContact, default_country: Option<&str>) -> Result<NewContact, ContactsError> {
+    let name = bounded_text("name", &details.name, MAX_NAME_LEN)?;
+    let structured_name = validate_structured_name(&details.structured_name)?;
+    if name.is_empty() && structured_name.is_empty() {
+        return Err(invalid("name", "must not be empty"));
+    }
+
+    let counts = [
+        ("emails", details.emails.len()),
+        ("phones", details.phones.len()),
+        ("addresses", details.addresses.len()),
+    ];
+    for (field, count) in counts {
+        if count > MAX_VALUES_PER_FIELD {
+            return Err(invalid(field, format!("must have at most {} entries", MAX_VALUES_PER_FIELD)));
+        }
+    }
+    let emails = details
+        .emails
+        .iter()
+        .enumerate()
+        .map(|(i, email)| {
+            let field = format!("emails[{}]", i);
+            Ok(LabelledValue {
+                label: bounded_text(&format!("{}.label", field), &email.label, MAX_LABEL_LEN)?,
+                value: normalize_email(&email.value).map_err(|reason| invalid(field, reason))?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let phones = details
+        .phones
+        .iter()
+        .enumerate()
+        .map(|(i, phone)| {
+            let field = format!("phones[{}]", i);
+            Ok(LabelledValue {
+                label: bounded_text(&format!("{}.label", field), &phone.label, MAX_LABEL_LEN)?,
+                value: normalize_phone(&phone.value, default_country).map_err(|reason| invalid(field, reason))?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let addresses = details
+        .addresses
+        .iter()
+        .enumerate()
+        .map(|(i, address)| validate_address(&format!("addresses[{}]", i), address))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(NewContact {
+        name,
+        structured_name,
+        emails,
+        phones,
+        addresses,
+        organization: bounded_text("organization", &details.organization, MAX_ORGANIZATION_LEN)?,
+        title: bounded_text("title", &details.title, MAX_TITLE_LEN)?,
+    })
+}
+
+/// Trim a text field and check it is at most `max_len` characters long.
+fn bounded_text(field: &str, value: &str, max_len: usize) -> Result<String, ContactsError> {
+    let value = value.trim();
+    if value.chars().count() > max_len {
+        return Err(invalid(field, format!("must be at most {} characters", max_len)));
+    }
+    Ok(value.to_string())
+}
+
+fn validate_structured_name(name: &StructuredName) -> Result<StructuredName, ContactsError> {
+    let part = |field: &str, value: &str| bounded_text(&format!("structured_name.{}", field), value, MAX_NAME_PART_LEN);
+    Ok(StructuredName {
+        prefix: part("prefix", &name.prefix)?,
+        given: part("given", &name.given)?,
+        additional: part("additional", &name.additional)?,
+        family: part("family", &name.family)?,
+        suffix: part("suffix", &name.suffix)?,
+    })
+}
+
+fn validate_address(field: &str, address: &PostalAddress) -> Result<PostalAddress, ContactsError> {
+    let part = |name: &str, value: &str| bounded_text(&format!("{}.{}", field, name), value, MAX_ADDRESS_PART_LEN);
+    let address = PostalAddress {
+        label: bounded_text(&format!("{}.label", field), &address.label, MAX_LABEL_LEN)?,
+        street: part("street", &address.street)?,
+        locality: part("locality", &address.locality)?,
+        region: part("region", &address.region)?,
+        postal_code: part("postal_code", &address.postal_code)?,
+        country: part("country", &address.country)?,
+    };
+    if address.is_empty() {
+        return Err(invalid(field, "must not be empty"));
+    }
+    Ok(address)
+}
+
+/// Check the syntax of an email address and lowercase its domain, which is case-insensitive.
+///
+/// Only the common dot-atom form is accepted; quoted local parts and IP address literals are
+/// valid per RFC 5322 but are rejected as almost always being typos.
+pub fn normalize_email(email: &str) -> Result<String, String> {
+    let email = email.trim();
+    if email.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if email.chars().count() > MAX_EMAIL_LEN {
+        return Err(format!("must be at most {} characters", MAX_EMAIL_LEN));
+    }
+    let (local, domain) = email.rsplit_once('@').ok_or("must contain an @")?;
+
+    if local.is_empty() || local.chars().count() > MAX_EMAIL_LOCAL_PART_LEN {
+        return Err(format!("must have 1 to {} characters before the @", MAX_EMAIL_LOCAL_PART_LEN));
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err("must not start or end with a dot or have two dots in a row before the @".to_string());
+    }
+    if let Some(c) = local.chars().find(|&c| !c.is_alphanumeric() && !EMAIL_LOCAL_PART_SYMBOLS.contains(c)) {
+        return Err(format!("must not contain '{}' before the @", c));
+    }
+
+    let domain = domain.to_lowercase();
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 {
+        return Err("must have a domain with at least two parts, e.g. example.com".to_string());
+    }
+    for label in &labels {
+        if label.is_empty() || label.chars().count() > MAX_DOMAIN_LABEL_LEN {
+            return Err(format!("must have domain parts of 1 to {} characters", MAX_DOMAIN_LABEL_LEN));
+        }
+        if label.starts_with('-') || label.ends_with('-') || !label.chars().all(|c| c.is_alphanumeric() || c == '-') {
+            return Err(format!("must not have an invalid domain part '{}'", label));
+        }
+    }
+    if labels.last().is_some_and(|tld| tld.chars().all(|c| c.is_ascii_digit())) {
+        return Err("must not have a numeric top-level domain".to_string());
+    }
+    Ok(format!("{}@{}", local, domain))
+}
+
+/// Parse a phone number and format it as E.164, e.g. "+15551234567".
+///
+/// Spaces, dashes, dots, slashes and parentheses are ignored. Numbers starting with "+" or
+/// the international prefix "00" carry their own country code, and a trunk prefix written
+/// after it as "(0)" is dropped. Any other number is read as a national number of
+/// `default_country`, dropping its trunk prefix.
+pub fn normalize_phone(phone: &str, default_country: Option<&str>) -> Result<String, String> {
+    let phone = phone.trim();
+    if phone.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if phone.chars().count() > MAX_PHONE_LEN {
+        return Err(format!("must be at most {} characters", MAX_PHONE_LEN));
+    }
+
+    let (international, rest) = match phone.strip_prefix('+') {
+        Some(rest) => (true, rest.replacen("(0)", "", 1)),
+        None => (false, phone.to_string()),
+    };
+    let mut digits = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            ' ' | '-' | '.' | '/' | '(' | ')' => {}
+            _ => return Err(format!("must not contain '{}'", c)),
+        }
+    }
+
+    let digits = match (international, digits.strip_prefix("00")) {
+        (true, _) => digits,
+        (false, Some(rest)) => rest.to_string(),
+        (false, None) => {
+            let country = default_country.ok_or("must start with + and a country code, or set a default country")?;
+            national_to_international(&digits, country)?
+        }
+    };
+    if digits.starts_with('0') {
+        return Err("must not have a country code starting with 0".to_string());
+    }
+    if !(MIN_E164_DIGITS..=MAX_E164_DIGITS).contains(&digits.len()) {
+        return Err(format!(
+            "must have {} to {} digits including the country code",
+            MIN_E164_DIGITS, MAX_E164_DIGITS
+        ));
+    }
+    Ok(format!("+{}", digits))
+}
+
+fn national_to_international(digits: &str, country: &str) -> Result<String, String> {
+    let &(_, calling_code, trunk_prefix) = COUNTRIES
+        .iter()
+        .find(|(code, _, _)| *code == country)
+        .ok_or_else(|| format!("has no known country code for the default country {}", country))?;
+
+    // North American numbers are ten digits, and the trunk prefix 1 is optional
+    if calling_code == "1" {
+        let national = match digits.len() {
+            11 => digits.strip_prefix(trunk_prefix).unwrap_or(digits),
+            _ => digits,
+        };
+        if national.len() != 10 {
+            return Err("must have 10 digits after the optional leading 1".to_string());
+        }
+        return Ok(format!("{}{}", calling_code, national));
+    }
+
+    let national = match trunk_prefix {
+        "" => digits,
+        prefix => digits.strip_prefix(prefix).unwrap_or(digits),
+    };
+    if national.len() < MIN_NATIONAL_DIGITS {
+        return Err("is too short".to_string());
+    }
+    Ok(format!("{}{}", calling_code, national))
+}
+
+/// Check an ISO 3166-1 alpha-2 country code against the countries with known phone number
+/// rules, returning it upper-cased.
+pub fn normalize_country(country: &str) -> Result<String, String> {
+    let country = country.trim().to_ascii_uppercase();
+    if !COUNTRIES.iter().any(|(code, _, _)| *code == country) {
+        return Err("must be a supported ISO 3166-1 alpha-2 country code, e.g. US".to_string());
+    }
+    Ok(country)
+}
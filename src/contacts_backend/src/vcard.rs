@@ -0,0 +1,365 @@
+use candid::{CandidType, Deserialize};
+
+use crate::data::contact::Contact;
+use crate::datetime;
+use crate::data::labelled_value::LabelledValue;
+use crate::data::new_contact::NewContact;
+use crate::data::postal_address::PostalAddress;
+use crate::data::structured_name::StructuredName;
+
+/// Maximum length of a vCard content line in octets, excluding the line break.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// vCard format version to produce.
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum VCardVersion {
+    /// vCard 3.0 (RFC 2426), for older phones and mail clients.
+    V3,
+    /// vCard 4.0 (RFC 6350).
+    V4,
+}
+
+/// Serialize a contact as a single vCard.
+pub fn to_vcard(contact: &Contact, version: VCardVersion) -> String {
+    let name = &contact.structured_name;
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        match version {
+            VCardVersion::V3 => "VERSION:3.0".to_string(),
+            VCardVersion::V4 => "VERSION:4.0".to_string(),
+        },
+        format!("FN:{}", escape(&contact.name)),
+        format!(
+            "N:{};{};{};{};{}",
+            escape(&name.family),
+            escape(&name.given),
+            escape(&name.additional),
+            escape(&name.prefix),
+            escape(&name.suffix)
+        ),
+    ];
+    if !contact.organization.is_empty() {
+        lines.push(format!("ORG:{}", escape(&contact.organization)));
+    }
+    if !contact.title.is_empty() {
+        lines.push(format!("TITLE:{}", escape(&contact.title)));
+    }
+    for email in contact.emails.iter().filter(|e| !e.value.is_empty()) {
+        let mut types = type_values(&email.label);
+        if version == VCardVersion::V3 {
+            types.insert(0, "INTERNET".to_string());
+        }
+        lines.push(format!("EMAIL{}:{}", type_param(&types), escape(&email.value)));
+    }
+    for phone in contact.phones.iter().filter(|p| !p.value.is_empty()) {
+        let types = type_param(&type_values(&phone.label));
+        lines.push(match version {
+            VCardVersion::V3 => format!("TEL{}:{}", types, escape(&phone.value)),
+            // TEL defaults to a URI in 4.0, but stored numbers are free text
+            VCardVersion::V4 => format!("TEL;VALUE=text{}:{}", types, escape(&phone.value)),
+        });
+    }
+    for address in contact.addresses.iter().filter(|a| !a.is_empty()) {
+        // ADR is pobox;extended;street;locality;region;postal-code;country
+        lines.push(format!(
+            "ADR{}:;;{};{};{};{};{}",
+            type_param(&type_values(&address.label)),
+            escape(&address.street),
+            escape(&address.locality),
+            escape(&address.region),
+            escape(&address.postal_code),
+            escape(&address.country)
+        ));
+    }
+    if contact.updated_at() > 0 {
+        lines.push(format!("REV:{}", datetime::format_utc(contact.updated_at(), false)));
+    }
+    lines.push("END:VCARD".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// The `TYPE` values for a label. vCard calls a mobile number a "cell" phone; labels that
+/// are not valid parameter values are dropped.
+fn type_values(label: &str) -> Vec<String> {
+    let label = label.trim().to_lowercase();
+    if label == "mobile" {
+        return vec!["cell".to_string()];
+    }
+    if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Vec::new();
+    }
+    vec![label]
+}
+
+fn type_param(types: &[String]) -> String {
+    if types.is_empty() {
+        String::new()
+    } else {
+        format!(";TYPE={}", types.join(","))
+    }
+}
+
+/// Parse vCard text containing any number of cards, in order.
+///
+/// Each card yields either the contact details it holds or the reason it could not be
+/// parsed. The name comes from `FN`, falling back to `N`. Every `EMAIL`, `TEL` and `ADR` is
+/// kept in order, labelled with its first meaningful `TYPE`. `ORG` and `TITLE` are read as
+/// well; other properties are ignored.
+pub fn parse_vcards(text: &str) -> Vec<Result<NewContact, String>> {
+    let mut cards = Vec::new();
+    let mut current: Option<Vec<Property>> = None;
+
+    for line in unfold(text).lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((name, types, value)) = split_content_line(line) else {
+            if current.is_some() {
+                cards.push(Err(format!("malformed line: {}", line)));
+                current = None;
+            }
+            continue;
+        };
+
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VCARD") => {
+                if current.is_some() {
+                    cards.push(Err("missing END:VCARD".to_string()));
+                }
+                current = Some(Vec::new());
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                cards.push(card_to_contact(&current.take().unwrap()));
+            }
+            (_, Some(properties)) => properties.push(Property {
+                name,
+                label: label_from_types(&types),
+                value: value.to_string(),
+            }),
+            // content outside of a card is ignored
+            (_, None) => {}
+        }
+    }
+    if current.is_some() {
+        cards.push(Err("missing END:VCARD".to_string()));
+    }
+    cards
+}
+
+/// A content line of a card: upper-cased name, label derived from its `TYPE` parameters,
+/// and raw value.
+struct Property {
+    name: String,
+    label: String,
+    value: String,
+}
+
+fn card_to_contact(properties: &[Property]) -> Result<NewContact, String> {
+    let all = |name: &'static str| {
+        properties
+            .iter()
+            .filter(move |p| p.name == name && !p.value.trim().is_empty())
+    };
+    let first = |name: &'static str| all(name).next().map(|p| p.value.as_str());
+
+    // N is family;given;additional;prefixes;suffixes
+    let structured_name = first("N")
+        .map(|structured| {
+            let parts = components(structured);
+            let part = |i: usize| parts.get(i).cloned().unwrap_or_default();
+            StructuredName {
+                prefix: part(3),
+                given: part(1),
+                additional: part(2),
+                family: part(0),
+                suffix: part(4),
+            }
+        })
+        .unwrap_or_default();
+    let name = match first("FN") {
+        Some(full_name) => unescape(full_name).trim().to_string(),
+        None => structured_name.formatted(),
+    };
+    if name.is_empty() {
+        return Err("missing FN or N".to_string());
+    }
+
+    let emails = all("EMAIL")
+        .map(|p| LabelledValue::new(&p.label, unescape(&p.value).trim()))
+        .collect();
+    let phones = all("TEL")
+        .map(|p| {
+            let tel = unescape(&p.value);
+            // vCard 4.0 numbers are often `tel:` URIs
+            let tel = tel.strip_prefix("tel:").unwrap_or(&tel);
+            LabelledValue::new(&p.label, tel.trim())
+        })
+        .collect();
+    // ADR is pobox;extended;street;locality;region;postal-code;country
+    let addresses = all("ADR")
+        .map(|p| {
+            let parts = components(&p.value);
+            let part = |i: usize| parts.get(i).cloned().unwrap_or_default();
+            let street = [part(0), part(1), part(2)]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            PostalAddress {
+                label: p.label.clone(),
+                street,
+                locality: part(3),
+                region: part(4),
+                postal_code: part(5),
+                country: part(6),
+            }
+        })
+        .filter(|a| !a.is_empty())
+        .collect();
+    // ORG is organization;unit;..., only the organization is kept
+    let organization = first("ORG")
+        .and_then(|org| components(org).into_iter().next())
+        .unwrap_or_default();
+    let title = first("TITLE").map(|t| unescape(t).trim().to_string()).unwrap_or_default();
+
+    Ok(NewContact {
+        name,
+        structured_name,
+        emails,
+        phones,
+        addresses,
+        organization,
+        title,
+    })
+}
+
+/// Split a structured value on unescaped semicolons and unescape each trimmed component.
+fn components(value: &str) -> Vec<String> {
+    split_unescaped(value, ';')
+        .iter()
+        .map(|part| unescape(part).trim().to_string())
+        .collect()
+}
+
+/// Pick a label from a property's `TYPE` values, skipping those that describe the kind of
+/// value rather than its use. A "cell" phone is labelled "mobile".
+fn label_from_types(types: &[String]) -> String {
+    const NOT_LABELS: [&str; 6] = ["internet", "pref", "voice", "text", "x400", "msg"];
+    types
+        .iter()
+        .map(|t| t.trim().trim_matches('"').to_lowercase())
+        .find(|t| !t.is_empty() && !NOT_LABELS.contains(&t.as_str()))
+        .map(|t| if t == "cell" { "mobile".to_string() } else { t })
+        .unwrap_or_default()
+}
+
+/// Join folded lines: a line break followed by a space or tab continues the previous line.
+fn unfold(text: &str) -> String {
+    text.replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "")
+}
+
+/// Split a content line into its upper-cased property name, without group or parameters,
+/// its `TYPE` parameter values and its raw value. Returns `None` if the line has no value.
+fn split_content_line(line: &str) -> Option<(String, Vec<String>, &str)> {
+    // the value starts at the first colon that is not inside a quoted parameter value
+    let mut in_quotes = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(i),
+        _ => None,
+    })?;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut params = head.split(';');
+    let name = params.next().unwrap_or("");
+    let name = name.rsplit('.').next().unwrap_or(name);
+    let types = params
+        .flat_map(|param| match param.split_once('=') {
+            Some((key, values)) if key.trim().eq_ignore_ascii_case("TYPE") => values.split(',').collect(),
+            Some(_) => Vec::new(),
+            // vCard 2.1 writes types as bare parameters, e.g. `TEL;CELL:`
+            None => vec![param],
+        })
+        .map(str::to_string)
+        .collect();
+    Some((name.trim().to_ascii_uppercase(), types, value))
+}
+
+/// Split a raw value on a separator that is not escaped with a backslash.
+fn split_unescaped(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == separator => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Reverse the escaping of a text value per RFC 6350 section 3.4.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Escape a text value per RFC 6350 section 3.4.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ',' => escaped.push_str("\\,"),
+            ';' => escaped.push_str("\\;"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Fold a content line into chunks of at most 75 octets, never splitting a UTF-8
+/// character, and terminate each physical line with CRLF.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut line_octets = 0;
+    for c in line.chars() {
+        if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // the leading space counts towards the continuation line's length
+            line_octets = 1;
+        }
+        folded.push(c);
+        line_octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}